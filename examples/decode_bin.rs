@@ -1,4 +1,3 @@
-#![feature(generic_const_exprs)]
 use nanoft8::{
     protocol::{
         message::{callsign::hashtable::HashTable, Message},
@@ -1,4 +1,3 @@
-#![feature(generic_const_exprs)]
 use chrono::Timelike as _;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait as _};
 use nanoft8::{
@@ -8,8 +7,8 @@ use nanoft8::{
         crc::check_crc,
         message::{callsign::hash::CallsignHashTable, Message},
     },
+    spectrogram::Spectrogram,
 };
-use num_complex::Complex32;
 use std::collections::BTreeMap;
 
 #[inline]
@@ -62,7 +61,7 @@ fn main() {
         loop {
             let mut last_sec = 0;
             let mut iter = std::iter::from_fn(|| {
-                if last_sec != 0 && sec() % 15 == 0 {
+                if last_sec != 0 && sec().is_multiple_of(15) {
                     return None;
                 }
                 last_sec = sec() % 15;
@@ -115,52 +114,29 @@ fn process(
     rate: u32,
     hashtable: &mut impl CallsignHashTable,
 ) {
+    let Some(mut spectrogram) = Spectrogram::with_rate(rate) else {
+        println!("unsupported sample rate: {}", rate);
+        return;
+    };
     let mut decoder = Dec::default();
 
-    let step: usize = (rate * 40 / 1000) as usize;
-    let size: usize = (rate * 160 / 1000) as usize;
-
-    let mut buf = vec![0f32; size];
-
-    let mut fftbuf = vec![Complex32::new(0.0, 0.0); size * 2];
-
-    let mut planner = rustfft::FftPlanner::new();
-    let fft = planner.plan_fft_forward(size * 2);
-
-    let mut spectrum = [SpecFloat::default(); 1024];
-
-    'outer: for i in 0.. {
-        for j in 0..step {
-            match source.next() {
-                Some(v) => buf[(i % 4) * step + j] = v,
-                None => break 'outer,
-            }
-        }
-
-        fftbuf
+    let mut buf = [0f32; 1024];
+    loop {
+        let n = buf
             .iter_mut()
-            .for_each(|x| *x = Complex32::new(0.0, 0.0));
-        for j in 0..size {
-            fftbuf[j].re = buf[((i + 1) * step + j) % size] as f32;
+            .zip(&mut *source)
+            .map(|(x, v)| *x = v)
+            .count();
+        spectrogram.put_samples(&buf[..n], &mut decoder);
+        if n < buf.len() {
+            break;
         }
-        hanning_window(&mut fftbuf[..size]);
-        fft.process(&mut fftbuf);
-
-        fftbuf[..1024].iter().enumerate().for_each(|(i, x)| {
-            let x = (x / 10.).norm();
-            spectrum[i] = x;
-        });
-        decoder.put_spectrum(&spectrum);
     }
     print_candidates(&decoder, hashtable);
 }
 
-fn hanning_window(data: &mut [Complex32]) {
-    let n = data.len();
-    for (i, x) in data.iter_mut().enumerate() {
-        *x *= 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n as f32).cos();
-    }
-}
+const OSD_CANDIDATES: usize = 10;
+const OSD_DEPTH: usize = 2;
 
 const COLOR_GRAY: &str = "\x1b[38;5;240m";
 const COLOR_RESET: &str = "\x1b[0m";
@@ -176,8 +152,8 @@ fn print_candidates(dec: &Dec, hashtable: &mut impl CallsignHashTable) {
 
     let mut buf = [0; 256];
     println!(
-        "{:>5} {:>8} {:>8} {:>8}  {:>3} {}",
-        "dt", "freq", "strength", "reliab", "err", "message"
+        "{:>5} {:>8} {:>8} {:>8}  {:>3} message",
+        "dt", "freq", "strength", "reliab", "err"
     );
     let mut cnt = 0;
     for i in c.iter() {
@@ -186,9 +162,18 @@ fn print_candidates(dec: &Dec, hashtable: &mut impl CallsignHashTable) {
         }
         cnt += 1;
 
-        let (bs, err) = protocol::ldpc::solve(&i.data);
+        let (mut bs, mut err) = protocol::ldpc::solve(&i.data);
 
-        let res = check_crc(&bs);
+        let mut res = check_crc(&bs);
+
+        // OSD is expensive; try only for the strongest candidates
+        if !res && cnt <= OSD_CANDIDATES {
+            if let Some((b, e)) = protocol::ldpc::solve_osd(&i.data, OSD_DEPTH) {
+                bs = b;
+                err = e;
+                res = true;
+            }
+        }
 
         if bs.0.iter().all(|&x| x == 0) {
             // empty message
@@ -199,7 +184,7 @@ fn print_candidates(dec: &Dec, hashtable: &mut impl CallsignHashTable) {
             continue;
         }
 
-        let bs = bs.with_size::<77>();
+        let bs = bs.with_size::<77, 3>();
         let msg = Message::decode(&bs);
 
         let str = msg
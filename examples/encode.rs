@@ -1,5 +1,5 @@
-#![feature(generic_const_exprs)]
 use nanoft8::protocol::{
+    self,
     crc::{add_crc, check_crc},
     ldpc,
     message::{callsign::C28, Message, G15},
@@ -46,4 +46,16 @@ fn main() {
     msg.write_str(&mut str, None::<&()>);
     let str = String::from_utf8_lossy(&str);
     println!("decoded: {}", str);
+
+    // or all at once
+    let tones = protocol::encode_message(&msg);
+    assert_eq!(tones, protocol::encode_symbols(&buf));
+    assert_eq!(
+        Some(tones),
+        protocol::encode_text(b"JA1ZLO JA1YWX R PM95", None::<&mut ()>)
+    );
+    println!(
+        "tones: {}",
+        tones.iter().map(|x| x.to_string()).collect::<String>()
+    );
 }
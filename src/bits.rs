@@ -1,28 +1,27 @@
 // NOTE: inner value is bit-reversed (i.e. 1<<31 represents the first bit (i.e. index 0))
+// WORDS must be equal to `num_words(SIZE)`; this is checked at compile time
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Bitset<const SIZE: usize>(pub [u32; num_words::<SIZE>()])
-where
-    [u32; num_words::<SIZE>()]: Sized;
+pub struct Bitset<const SIZE: usize, const WORDS: usize>(pub [u32; WORDS]);
 
-pub const fn num_words<const SIZE: usize>() -> usize {
-    (SIZE + 31) / 32
+pub const fn num_words(size: usize) -> usize {
+    size.div_ceil(32)
 }
 
-impl<const SIZE: usize> Default for Bitset<SIZE>
-where
-    [u32; num_words::<SIZE>()]: Sized,
-{
+impl<const SIZE: usize, const WORDS: usize> Default for Bitset<SIZE, WORDS> {
     fn default() -> Self {
-        Self([0; num_words::<SIZE>()])
+        #![allow(path_statements)]
+        Self::_ASSERT;
+        Self([0; WORDS])
     }
 }
 
-impl<const SIZE: usize> Bitset<SIZE>
-where
-    [u32; num_words::<SIZE>()]: Sized,
-{
+impl<const SIZE: usize, const WORDS: usize> Bitset<SIZE, WORDS> {
+    const _ASSERT: () = {
+        assert!(WORDS == num_words(SIZE), "WORDS must be num_words(SIZE)");
+    };
+
     pub const SIZE: usize = SIZE;
-    pub const LEN: usize = num_words::<SIZE>();
+    pub const LEN: usize = WORDS;
 
     #[inline]
     pub fn slice(&self, start: usize, size: usize) -> u32 {
@@ -106,14 +105,18 @@ where
         }
     }
 
-    pub fn with_size<const NEW_SIZE: usize>(self) -> Bitset<NEW_SIZE>
-    where
-        [u32; num_words::<NEW_SIZE>()]: Sized,
-    {
-        let mut new = Bitset::<NEW_SIZE>::default();
-        let min = Self::LEN.min(num_words::<NEW_SIZE>());
+    #[inline]
+    pub fn count_ones(&self) -> u32 {
+        self.0.iter().map(|x| x.count_ones()).sum()
+    }
+
+    pub fn with_size<const NEW_SIZE: usize, const NEW_WORDS: usize>(
+        self,
+    ) -> Bitset<NEW_SIZE, NEW_WORDS> {
+        let mut new = Bitset::<NEW_SIZE, NEW_WORDS>::default();
+        let min = Self::LEN.min(NEW_WORDS);
         new.0[..min].copy_from_slice(&self.0[..min]);
-        if NEW_SIZE < Self::SIZE {
+        if NEW_SIZE < Self::SIZE && !NEW_SIZE.is_multiple_of(32) {
             if let Some(v) = new.0.last_mut() {
                 let mask = (!0) << (32 - (NEW_SIZE % 32));
                 *v &= mask;
@@ -123,11 +126,19 @@ where
     }
 }
 
-impl<const SIZE: usize> From<[u32; num_words::<SIZE>()]> for Bitset<SIZE>
-where
-    [u32; num_words::<SIZE>()]: Sized,
-{
-    fn from(arr: [u32; num_words::<SIZE>()]) -> Self {
+impl<const SIZE: usize, const WORDS: usize> core::ops::BitXorAssign for Bitset<SIZE, WORDS> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        for (x, y) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *x ^= *y;
+        }
+    }
+}
+
+impl<const SIZE: usize, const WORDS: usize> From<[u32; WORDS]> for Bitset<SIZE, WORDS> {
+    fn from(arr: [u32; WORDS]) -> Self {
+        #![allow(path_statements)]
+        Self::_ASSERT;
         Self(arr)
     }
 }
@@ -148,10 +159,7 @@ fn set_range(v: &mut u32, start: usize, size: usize, value: u32) {
 }
 
 #[cfg(not(feature = "no_std"))]
-impl<const SIZE: usize> std::fmt::Display for Bitset<SIZE>
-where
-    [u32; num_words::<SIZE>()]: Sized,
-{
+impl<const SIZE: usize, const WORDS: usize> std::fmt::Display for Bitset<SIZE, WORDS> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for i in 0..Self::SIZE {
             write!(f, "{}", if self.get(i) { "1" } else { "0" })?;
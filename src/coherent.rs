@@ -0,0 +1,276 @@
+// coherent demodulation from time-domain samples, for candidates found on magnitude spectra
+//
+// FT8 keeps its phase continuous and each symbol holds a whole number of cycles of the tone spacing,
+// so with the carrier referenced to the slot, every symbol starts at the same phase whatever its tone.
+// the Costas arrays give that phase and the frequency offset that makes it drift; each data symbol is
+// then correlated with the 8 tones and only the part in phase with the reference is counted, which
+// halves the noise the magnitude detector of `Decoder` sees.
+//
+// input is 8 kHz samples, as `Spectrogram` takes them after resampling.
+
+use crate::{
+    float::FloatS,
+    protocol::{self, FSK_ARITY, MESSAGE_LEN, PAYLOAD_BITS},
+    spectrogram::{twiddle, Complex, SAMPLE_RATE},
+};
+use core::f32::consts::PI;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+// samples per symbol; a tone spacing is one cycle of it
+const SYMBOL_SIZE: usize = 1280;
+
+// searched offsets of the candidate frequency in Hz, and of its start in seconds, and their steps
+// a weak candidate may start a 40 ms row off, past its sub-row offset
+const FREQ_RANGE: f32 = 1.0;
+const FREQ_SEARCH_STEP: f32 = 0.025;
+const TIME_RANGE: f32 = 0.05;
+const TIME_SEARCH_STEP: f32 = 0.002;
+
+// first symbol of each Costas array
+const MARKERS: [usize; 3] = [
+    0,
+    protocol::COSTAS_SIZE + protocol::PAYLOAD_HALF_LEN,
+    (protocol::COSTAS_SIZE + protocol::PAYLOAD_HALF_LEN) * 2,
+];
+
+/// estimates of `demodulate`, on the scale of `Spectrogram` (a sine of amplitude A reads A)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coherent {
+    pub dt: f32,    // refined start time, in seconds
+    pub freq: f32,  // refined audio frequency of tone 0, in Hz
+    pub amp: f32,   // amplitude of the signal
+    pub noise: f32, // rms noise of a tone correlation
+}
+
+impl Coherent {
+    // signal to noise ratio in the 2500 Hz reference bandwidth, in dB
+    pub fn snr_db(&self) -> f32 {
+        // a correlation over one symbol sees the noise of 6.25 Hz
+        let ratio = self.amp * self.amp / (self.noise * self.noise).max(1e-30);
+        10.0 * (ratio * protocol::TONE_SPACING / 2500.0).log10()
+    }
+}
+
+// correlation of each symbol with the 8 tones, the carrier referenced to `samples[0]`
+fn correlate(samples: &[f32], start: usize, freq: f32) -> [[Complex; FSK_ARITY]; MESSAGE_LEN] {
+    let step = -2.0 * PI * freq / SAMPLE_RATE as f32;
+    let step = Complex::new(step.cos(), step.sin());
+    let scale = 2.0 / SYMBOL_SIZE as f32;
+
+    core::array::from_fn(|s| {
+        let i = start + s * SYMBOL_SIZE;
+        // carrier phase at the symbol start, in cycles; f64 keeps it exact over the slot
+        let cycles = freq as f64 * i as f64 / SAMPLE_RATE as f64;
+        let phase = -2.0 * PI * (cycles - (cycles as i64) as f64) as f32;
+        let mut carrier = Complex::new(phase.cos(), phase.sin());
+
+        let mut out = [Complex::new(0.0, 0.0); FSK_ARITY];
+        for (n, &x) in samples[i..i + SYMBOL_SIZE].iter().enumerate() {
+            let z = carrier.scale(x);
+            carrier = carrier * step;
+            for (t, o) in out.iter_mut().enumerate() {
+                // t cycles per symbol
+                *o = *o + z * twiddle(2 * t * n);
+            }
+        }
+        out.map(|o| o.scale(scale))
+    })
+}
+
+// the Costas tone of each marker symbol: its array, tone, seconds from the signal start and
+// correlation
+fn markers(
+    corr: &[[Complex; FSK_ARITY]; MESSAGE_LEN],
+) -> impl Iterator<Item = (usize, usize, f32, Complex)> + '_ {
+    MARKERS.into_iter().enumerate().flat_map(move |(a, m)| {
+        protocol::MARKER_COSTAS
+            .iter()
+            .enumerate()
+            .map(move |(k, &t)| {
+                let s = m + k;
+                (a, t, s as f32 * protocol::SYMBOL_PERIOD, corr[s][t])
+            })
+    })
+}
+
+#[inline]
+fn rotation(offset: f32, t: f32) -> Complex {
+    let phase = -2.0 * PI * offset * t;
+    Complex::new(phase.cos(), phase.sin())
+}
+
+/// coherent LLRs of a signal starting at `dt` seconds with tone 0 at `freq` Hz,
+/// e.g. from `Decoder::position` of a candidate; `out` takes the LLRs for `ldpc::solve`
+/// `samples` are at `SAMPLE_RATE`, from the `first`th sample of the slot
+/// None if the signal is not within `samples`
+pub fn demodulate<F: FloatS>(
+    samples: &[f32],
+    first: isize,
+    dt: f32,
+    freq: f32,
+    out: &mut [F; PAYLOAD_BITS],
+) -> Option<Coherent> {
+    let start = (dt * SAMPLE_RATE as f32).round() as isize - first;
+    let start = usize::try_from(start).ok()?;
+    if start + MESSAGE_LEN * SYMBOL_SIZE > samples.len() {
+        return None;
+    }
+
+    // offsets that line up the phases of the markers
+    // the carrier turns with the frequency offset; a late start turns each tone by its frequency
+    let corr = correlate(samples, start, freq);
+    let mut best = (0.0f32, 0.0f32, 0.0f32);
+    let freq_steps = (FREQ_RANGE / FREQ_SEARCH_STEP).round() as i32;
+    let time_steps = (TIME_RANGE / TIME_SEARCH_STEP).round() as i32;
+    for j in -time_steps..=time_steps {
+        let delay = j as f32 * TIME_SEARCH_STEP;
+        let tones: [Complex; FSK_ARITY] =
+            core::array::from_fn(|t| rotation(t as f32 * protocol::TONE_SPACING, delay));
+        for i in -freq_steps..=freq_steps {
+            let offset = i as f32 * FREQ_SEARCH_STEP;
+            let sum = markers(&corr).fold(Complex::new(0.0, 0.0), |acc, (_, tone, t, r)| {
+                acc + r * tones[tone] * rotation(offset, t)
+            });
+            if sum.norm() > best.2 {
+                best = (offset, delay, sum.norm());
+            }
+        }
+    }
+    let freq = freq + best.0;
+    let shift = (best.1 * SAMPLE_RATE as f32).round() as isize;
+    let start = usize::try_from(start as isize - shift).ok()?;
+    if start + MESSAGE_LEN * SYMBOL_SIZE > samples.len() {
+        return None;
+    }
+    let corr = correlate(samples, start, freq);
+
+    // phase of each marker, tracked linearly between them
+    let mut sums = [Complex::new(0.0, 0.0); 3];
+    for (a, _, _, r) in markers(&corr) {
+        sums[a] = sums[a] + r;
+    }
+    let mut phases = sums.map(|s| s.im.atan2(s.re));
+    for a in 1..3 {
+        let turns = ((phases[a - 1] - phases[a]) / (2.0 * PI)).round();
+        phases[a] += turns * 2.0 * PI;
+    }
+    let center = (protocol::COSTAS_SIZE / 2) as f32;
+    let reference = |s: usize| {
+        let x = s as f32 - center;
+        let span = MARKERS[1] as f32;
+        let phase = if x < span {
+            phases[0] + (phases[1] - phases[0]) * x / span
+        } else {
+            phases[1] + (phases[2] - phases[1]) * (x - span) / span
+        };
+        Complex::new(phase.cos(), -phase.sin())
+    };
+
+    // amplitude from the in-phase marker tones, noise from the other tones
+    let (mut amp, mut noise) = (0.0f32, 0.0f32);
+    for m in MARKERS {
+        for (k, &tone) in protocol::MARKER_COSTAS.iter().enumerate() {
+            let s = m + k;
+            amp += (corr[s][tone] * reference(s)).re;
+            for (t, r) in corr[s].iter().enumerate() {
+                if t != tone {
+                    noise += r.re * r.re + r.im * r.im;
+                }
+            }
+        }
+    }
+    let count = (MARKERS.len() * protocol::COSTAS_SIZE) as f32;
+    let amp = (amp / count).max(0.0);
+    let noise = noise / (count * (FSK_ARITY - 1) as f32);
+
+    // log-likelihood of each tone from its in-phase part: 2 A Re / sigma^2
+    let gain = 2.0 * amp / noise.max(1e-30);
+    let data = (MARKERS[0] + protocol::COSTAS_SIZE..MARKERS[1])
+        .chain(MARKERS[1] + protocol::COSTAS_SIZE..MARKERS[2]);
+    for (s, out) in data.zip(out.chunks_exact_mut(protocol::FSK_DEPTH)) {
+        let r = reference(s);
+        let metric: [f32; FSK_ARITY] = core::array::from_fn(|t| (corr[s][t] * r).re * gain);
+        for (j, o) in out.iter_mut().enumerate() {
+            let mut max = [f32::MIN; 2];
+            for (t, &m) in metric.iter().enumerate() {
+                let bit = (protocol::GRAY_CODE_INV[t] & (4 >> j) != 0) as usize;
+                max[bit] = max[bit].max(m);
+            }
+            let mut sum = [0.0f32; 2];
+            for (t, &m) in metric.iter().enumerate() {
+                let bit = (protocol::GRAY_CODE_INV[t] & (4 >> j) != 0) as usize;
+                sum[bit] += (m - max[bit]).exp();
+            }
+            *o = (max[1] + sum[1].ln() - max[0] - sum[0].ln()).into();
+        }
+    }
+
+    Some(Coherent {
+        dt: (start as isize + first) as f32 / SAMPLE_RATE as f32,
+        freq,
+        amp,
+        noise: noise.sqrt(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decoder::Decoder,
+        protocol::{crc::check_crc, encode_text, ldpc},
+        spectrogram::Spectrogram,
+        testutil::{synth_audio, Rng},
+    };
+    use std::collections::BTreeMap;
+
+    fn solves(llr: &[f32]) -> bool {
+        let (bits, _) = ldpc::solve(llr);
+        check_crc(&bits) && !bits.0.iter().all(|&x| x == 0)
+    }
+
+    #[test]
+    fn test_demodulate() {
+        let tones = encode_text(b"CQ JA1ZLO PM95", None::<&mut ()>).unwrap();
+
+        // below the sensitivity of the magnitude detector, seeds it misses and coherent solves
+        let mut better = 0;
+        for seed in 0..20 {
+            let audio = synth_audio(&tones, SAMPLE_RATE, 1000.0, 0.5, 0.035, 0.3, &mut Rng(seed));
+            let mut decoder = Decoder::<f32, f32>::new();
+            Spectrogram::new().put_samples(&audio, &mut decoder);
+            let c = decoder
+                .candidates()
+                .iter()
+                .filter(|c| (decoder.position(c).1 - 1000.0).abs() < 3.0)
+                .max_by(|a, b| a.reliability.total_cmp(&b.reliability))
+                .unwrap();
+
+            let (dt, freq) = decoder.position(c);
+            let mut llr = [0.0f32; PAYLOAD_BITS];
+            let r = demodulate(&audio, 0, dt, freq, &mut llr).unwrap();
+            assert!((r.dt - 0.5).abs() <= 0.003, "{}", r.dt);
+            assert!((r.freq - 1000.0).abs() <= 0.1, "{}", r.freq);
+            let magnitude = decoder.decode_all(&mut BTreeMap::new()).count() > 0;
+            better += (solves(&llr) && !magnitude) as usize;
+        }
+        // 19 of 20 here, and 12 or more from 0.03 to 0.04
+        assert!(better >= 12, "{}", better);
+
+        // amplitude and noise on the scale of `Spectrogram`
+        let audio = synth_audio(&tones, SAMPLE_RATE, 1000.0, 0.5, 0.1, 0.3, &mut Rng(1));
+        let mut llr = [0.0f32; PAYLOAD_BITS];
+        let r = demodulate(&audio, 0, 0.51, 1000.3, &mut llr).unwrap();
+        assert!(solves(&llr));
+        assert!((r.amp - 0.1).abs() <= 0.01, "{}", r.amp);
+        // noise of rms 0.3, scaled by 2 / 1280 and summed over 1280 samples
+        let noise = 0.3 * 2.0 / 1280f32.sqrt();
+        assert!((r.noise - noise).abs() <= noise * 0.2, "{}", r.noise);
+
+        // the signal must be within the samples
+        assert!(demodulate(&audio[4200..], 4200, 0.51, 1000.3, &mut llr).is_none());
+        assert!(demodulate(&audio[..100000], 0, 0.51, 1000.3, &mut llr).is_none());
+    }
+}
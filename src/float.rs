@@ -1,3 +1,6 @@
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
 /// **signed** value that can be converted to/from f32
 pub trait FloatS: From<f32> + Into<f32> + Default + Copy {}
 impl FloatS for f32 {}
@@ -5,3 +8,292 @@ impl FloatS for f32 {}
 /// **unsigned** value that can be converted to/from f32
 pub trait FloatU: From<f32> + Into<f32> + PartialOrd + Default + Copy {}
 impl FloatU for f32 {}
+
+/// **signed** fixed-point value; `raw` is the value scaled by `2^FRAC_BITS`
+pub trait FixedS: FloatS {
+    const FRAC_BITS: u32;
+    fn raw(self) -> i16;
+}
+
+/// 8-bit fixed-point value with 3 fractional bits (i.e. -15.875..=15.875)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed8(pub i8);
+
+impl From<f32> for Fixed8 {
+    fn from(x: f32) -> Self {
+        let v = (x * (1 << Self::FRAC_BITS) as f32).round();
+        Self(v.clamp(-(i8::MAX as f32), i8::MAX as f32) as i8)
+    }
+}
+
+impl From<Fixed8> for f32 {
+    fn from(x: Fixed8) -> f32 {
+        x.0 as f32 / (1 << Fixed8::FRAC_BITS) as f32
+    }
+}
+
+impl FloatS for Fixed8 {}
+impl FixedS for Fixed8 {
+    const FRAC_BITS: u32 = 3;
+
+    #[inline]
+    fn raw(self) -> i16 {
+        self.0 as i16
+    }
+}
+
+/// 16-bit fixed-point value with 6 fractional bits (i.e. -511.98..=511.98)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed16(pub i16);
+
+impl From<f32> for Fixed16 {
+    fn from(x: f32) -> Self {
+        let v = (x * (1 << Self::FRAC_BITS) as f32).round();
+        Self(v.clamp(-(i16::MAX as f32), i16::MAX as f32) as i16)
+    }
+}
+
+impl From<Fixed16> for f32 {
+    fn from(x: Fixed16) -> f32 {
+        x.0 as f32 / (1 << Fixed16::FRAC_BITS) as f32
+    }
+}
+
+impl FloatS for Fixed16 {}
+impl FixedS for Fixed16 {
+    const FRAC_BITS: u32 = 6;
+
+    #[inline]
+    fn raw(self) -> i16 {
+        self.0
+    }
+}
+
+/// 8-bit log-magnitude: `20 log10(x)` in 0.5 dB steps, -119.5 dB (1) ..= +7.5 dB (255); 0 is zero
+/// matches the `Spectrogram` scaling, where a full scale tone reads 0 dB
+/// quantization error is within 0.25 dB (about 3%)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogU8(pub u8);
+
+impl LogU8 {
+    pub const DB_STEP: f32 = 0.5;
+    // dB of code 0; not representable, taken as zero
+    pub const DB_FLOOR: f32 = -120.0;
+}
+
+// magnitude of each code; so that decoding needs no `powf`
+static LOG_U8_TABLE: [f32; 256] = {
+    let mut table = [0.0; 256];
+    let mut i = 1;
+    while i < 256 {
+        let db = LogU8::DB_FLOOR as f64 + i as f64 * LogU8::DB_STEP as f64;
+        table[i] = const_exp(db / 20.0 * core::f64::consts::LN_10) as f32;
+        i += 1;
+    }
+    table
+};
+
+// exp by Taylor series, after halving `x` into -0.5..=0.5
+const fn const_exp(x: f64) -> f64 {
+    let mut x = x;
+    let mut k = 0;
+    while x > 0.5 || x < -0.5 {
+        x /= 2.0;
+        k += 1;
+    }
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut n = 1;
+    while n < 16 {
+        term *= x / n as f64;
+        sum += term;
+        n += 1;
+    }
+    while k > 0 {
+        sum *= sum;
+        k -= 1;
+    }
+    sum
+}
+
+impl From<f32> for LogU8 {
+    fn from(x: f32) -> Self {
+        if x <= 0.0 {
+            return Self(0);
+        }
+        let code = ((20.0 * x.log10() - Self::DB_FLOOR) / Self::DB_STEP).round();
+        Self(code.clamp(1.0, u8::MAX as f32) as u8)
+    }
+}
+
+impl From<LogU8> for f32 {
+    #[inline]
+    fn from(x: LogU8) -> f32 {
+        LOG_U8_TABLE[x.0 as usize]
+    }
+}
+
+impl FloatU for LogU8 {}
+
+/// IEEE 754 half precision (binary16): 1 sign, 5 exponent and 10 fraction bits
+/// normal range is 6.1e-5..=65504 (-84..=+96 dB); subnormals go down to 6.0e-8 with less precision
+/// conversion from f32 rounds to nearest even; overflow becomes infinity
+#[derive(Debug, Clone, Copy, Default)]
+pub struct F16(pub u16);
+
+impl From<f32> for F16 {
+    fn from(x: f32) -> Self {
+        let b = x.to_bits();
+        let sign = ((b >> 16) & 0x8000) as u16;
+        let exp = ((b >> 23) & 0xff) as i32;
+        let man = b & 0x7f_ffff;
+
+        if exp == 0xff {
+            // infinity or NaN
+            return Self(sign | 0x7c00 | if man != 0 { 0x200 } else { 0 });
+        }
+
+        let e = exp - 127 + 15;
+        if e >= 0x1f {
+            return Self(sign | 0x7c00);
+        }
+        if e <= 0 {
+            // subnormal, or zero
+            if e < -10 {
+                return Self(sign);
+            }
+            let man = man | 0x80_0000;
+            let shift = (14 - e) as u32;
+            let half = 1 << (shift - 1);
+            let rem = man & ((1 << shift) - 1);
+            let mut v = (man >> shift) as u16;
+            if rem > half || (rem == half && v & 1 != 0) {
+                v += 1;
+            }
+            return Self(sign | v);
+        }
+
+        // carry of the rounding may go into the exponent, which is still correct
+        let mut v = sign | ((e as u16) << 10) | (man >> 13) as u16;
+        let rem = man & 0x1fff;
+        if rem > 0x1000 || (rem == 0x1000 && v & 1 != 0) {
+            v += 1;
+        }
+        Self(v)
+    }
+}
+
+impl From<F16> for f32 {
+    fn from(x: F16) -> f32 {
+        let sign = ((x.0 & 0x8000) as u32) << 16;
+        let exp = ((x.0 >> 10) & 0x1f) as u32;
+        let man = (x.0 & 0x3ff) as u32;
+        match exp {
+            0 => {
+                // subnormal: man * 2^-24
+                let v = man as f32 / (1 << 24) as f32;
+                if sign != 0 {
+                    -v
+                } else {
+                    v
+                }
+            }
+            0x1f => f32::from_bits(sign | 0x7f80_0000 | (man << 13)),
+            _ => f32::from_bits(sign | ((exp + 127 - 15) << 23) | (man << 13)),
+        }
+    }
+}
+
+impl PartialEq for F16 {
+    fn eq(&self, other: &Self) -> bool {
+        f32::from(*self) == f32::from(*other)
+    }
+}
+
+impl PartialOrd for F16 {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        f32::from(*self).partial_cmp(&f32::from(*other))
+    }
+}
+
+impl FloatS for F16 {}
+impl FloatU for F16 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed16() {
+        for x in [0.0, 1.0, -1.0, 3.25, -100.5, 511.0] {
+            let y: f32 = Fixed16::from(x).into();
+            assert!((x - y).abs() <= 0.5 / 64.0, "{} {}", x, y);
+        }
+        assert_eq!(Fixed16::from(1e6).0, i16::MAX);
+        assert_eq!(Fixed16::from(-1e6).0, -i16::MAX);
+    }
+
+    #[test]
+    fn test_log_u8() {
+        assert_eq!(LogU8::from(0.0).0, 0);
+        assert_eq!(f32::from(LogU8(0)), 0.0);
+        // full scale is 0 dB
+        assert_eq!(LogU8::from(1.0).0, 240);
+        assert!((f32::from(LogU8(240)) - 1.0).abs() < 1e-6);
+        assert_eq!(LogU8::from(1e3).0, 255);
+        assert_eq!(LogU8::from(1e-9).0, 1);
+
+        let mut x = 1.1e-6f32;
+        while x < 2.0 {
+            let y: f32 = LogU8::from(x).into();
+            let err = 20.0 * (y / x).log10();
+            assert!(err.abs() <= 0.2501, "{} {} {}", x, y, err);
+            x *= 1.07;
+        }
+
+        // order is preserved
+        assert!(LogU8::from(0.5) < LogU8::from(0.6));
+    }
+
+    #[test]
+    fn test_f16() {
+        for (x, bits) in [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (0.5, 0x3800),
+            (65504.0, 0x7bff),
+            (1e6, 0x7c00),
+            (6.1035156e-5, 0x0400),
+            (5.9604645e-8, 0x0001),
+            (1e-9, 0x0000),
+            // halfway between 1 and the next; rounds to even
+            (1.0 + 1.0 / 2048.0, 0x3c00),
+            (1.0 + 3.0 / 2048.0, 0x3c02),
+        ] {
+            assert_eq!(F16::from(x).0, bits, "{}", x);
+        }
+        assert!(f32::from(F16::from(f32::NAN)).is_nan());
+        assert_eq!(f32::from(F16(0x7c00)), f32::INFINITY);
+
+        // all finite values round trip
+        for bits in 0..0x7c00u16 {
+            for bits in [bits, bits | 0x8000] {
+                let x: f32 = F16(bits).into();
+                assert_eq!(F16::from(x).0, bits);
+            }
+        }
+
+        let mut x = 1e-4f32;
+        while x < 6e4 {
+            let y: f32 = F16::from(x).into();
+            assert!(((y - x) / x).abs() <= 1.0 / 2048.0, "{} {}", x, y);
+            x *= 1.37;
+        }
+
+        assert!(F16::from(0.5) < F16::from(0.6));
+        assert!(F16::from(-0.5) < F16::from(0.1));
+        assert_eq!(F16::from(0.0), F16::from(-0.0));
+    }
+}
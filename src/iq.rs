@@ -0,0 +1,279 @@
+// complex I/Q front end for SDR receivers
+//
+// the I/Q stream is mixed so that the tuning `offset` lands at 0 Hz audio (upper sideband),
+// decimated to 4 kHz complex, and transformed into the same 3.125 Hz / 40 ms spectra as `Spectrogram`.
+// while decimating, the 0..3.2 kHz audio band is centered at DC, i.e. baseband = audio - 1.6 kHz,
+// so the other sideband never folds into the spectrum.
+//
+// scaling: a complex tone of amplitude A reads A, i.e. the same as a real tone of amplitude A
+// in `Spectrogram`.
+//
+// several sub-bands can be decoded from one wideband capture, by feeding the same samples
+// to one `IqSpectrogram` (and `Decoder`) per sub-band.
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::{
+    decoder::{Decoder, FREQ_STEP, SPECTRUM_SIZE},
+    float::{FloatS, FloatU},
+    resample::Decimator,
+    spectrogram::{twiddle, Complex, Fft, Sample},
+};
+use core::f32::consts::PI;
+
+const BASEBAND_RATE: u32 = 4000;
+const WINDOW_SIZE: usize = 640; // 160 ms
+const HOP_SIZE: usize = 160; // 40 ms
+const FFT_SIZE: usize = WINDOW_SIZE * 2; // zero padded
+
+// audio frequency at DC while decimating
+const CENTER: f32 = SPECTRUM_SIZE as f32 * FREQ_STEP / 2.0;
+
+// 1 / sum of the Hann window
+const SCALE: f32 = 2.0 / WINDOW_SIZE as f32;
+
+// up to 2^12 * 4 kHz, past 2.048 MHz and the other SDR rates
+const MAX_STAGES: usize = 12;
+// the oscillator is renormalized at this interval
+const RENORM_INTERVAL: usize = 1024;
+
+/// short time Fourier transform of complex I/Q samples, feeding `Decoder`
+/// no allocation
+#[derive(Clone)]
+pub struct IqSpectrogram {
+    rate: u32,
+    // mixer
+    osc: Complex,
+    step: Complex,
+    count: usize,
+    // decimators of I and Q
+    stages: [Option<[Decimator; 2]>; MAX_STAGES],
+    // ring buffer of the last WINDOW_SIZE baseband samples
+    samples: [Complex; WINDOW_SIZE],
+    pos: usize,
+    // spectra output so far
+    hops: usize,
+    fft: Fft,
+}
+
+impl IqSpectrogram {
+    /// `rate`: I/Q sample rate; a multiple of 4 kHz, with the ratio made of up to 12 factors of 2, 3 and 5
+    /// `offset`: I/Q frequency in Hz (may be negative) that reads as 0 Hz audio
+    /// None if the rate is not supported
+    pub fn new(rate: u32, offset: f32) -> Option<Self> {
+        if rate == 0 || !rate.is_multiple_of(BASEBAND_RATE) {
+            return None;
+        }
+
+        // larger factors first, so that the last stage at low rate is the shortest
+        let mut stages = [const { None }; MAX_STAGES];
+        let mut ratio = rate / BASEBAND_RATE;
+        let mut r = rate;
+        let mut n = 0;
+        for p in [5, 3, 2] {
+            while ratio.is_multiple_of(p) {
+                let d = || Decimator::new(r, p as usize, CENTER);
+                *stages.get_mut(n)? = Some([d(), d()]);
+                n += 1;
+                ratio /= p;
+                r /= p;
+            }
+        }
+        if ratio != 1 {
+            return None;
+        }
+
+        let mut s = Self {
+            rate,
+            osc: Complex::new(1.0, 0.0),
+            step: Complex::new(1.0, 0.0),
+            count: 0,
+            stages,
+            samples: [Complex::new(0.0, 0.0); WINDOW_SIZE],
+            pos: 0,
+            hops: 0,
+            fft: Fft::new(),
+        };
+        s.set_offset(offset);
+        Some(s)
+    }
+
+    /// retune; takes effect from the next sample
+    pub fn set_offset(&mut self, offset: f32) {
+        let cycles = (offset + CENTER) / self.rate as f32;
+        let a = -2.0 * PI * (cycles - cycles.round());
+        self.step = Complex::new(a.cos(), a.sin());
+    }
+
+    pub fn reset(&mut self) {
+        self.osc = Complex::new(1.0, 0.0);
+        self.count = 0;
+        for d in self.stages.iter_mut().flatten() {
+            d.iter_mut().for_each(Decimator::reset);
+        }
+        self.samples = [Complex::new(0.0, 0.0); WINDOW_SIZE];
+        self.pos = 0;
+        self.hops = 0;
+    }
+
+    /// feed I/Q pairs; `decoder` gets a spectrum every 40 ms, picked to fit its grid
+    pub fn put_samples<
+        S: Sample,
+        SpecFloat: FloatU,
+        LLRFloat: FloatS,
+        const WIDTH: usize,
+        const FREQ_OS: usize,
+        const TIME_OS: usize,
+        const ROWS: usize,
+        const CANDIDATES: usize,
+    >(
+        &mut self,
+        samples: &[[S; 2]],
+        decoder: &mut Decoder<SpecFloat, LLRFloat, WIDTH, FREQ_OS, TIME_OS, ROWS, CANDIDATES>,
+    ) {
+        let mut hops = self.hops;
+        self.put_samples_with(samples, |s| {
+            decoder.put_fine_spectrum(s, hops);
+            hops = hops.wrapping_add(1);
+        });
+        self.hops = hops;
+    }
+
+    /// feed I/Q pairs; `f` is called with the magnitude spectrum every 40 ms
+    pub fn put_samples_with<S: Sample>(
+        &mut self,
+        samples: &[[S; 2]],
+        mut f: impl FnMut(&[f32; SPECTRUM_SIZE]),
+    ) {
+        'outer: for &[i, q] in samples {
+            let mut x = Complex::new(i.to_f32(), q.to_f32()) * self.osc;
+            self.osc = self.osc * self.step;
+            self.count += 1;
+            if self.count == RENORM_INTERVAL {
+                self.count = 0;
+                self.osc = self.osc.scale(1.0 / self.osc.norm());
+            }
+
+            for [di, dq] in self.stages.iter_mut().flatten() {
+                // I and Q run in lockstep
+                let (Some(re), Some(im)) = (di.process(x.re), dq.process(x.im)) else {
+                    continue 'outer;
+                };
+                x = Complex::new(re, im);
+            }
+
+            self.samples[self.pos] = x;
+            self.pos = (self.pos + 1) % WINDOW_SIZE;
+            if self.pos.is_multiple_of(HOP_SIZE) {
+                let mut spectrum = [0.0; SPECTRUM_SIZE];
+                self.transform(&mut spectrum);
+                f(&spectrum);
+            }
+        }
+    }
+
+    // magnitude spectrum of the current window
+    fn transform(&mut self, out: &mut [f32; SPECTRUM_SIZE]) {
+        let (samples, pos) = (&self.samples, self.pos);
+        self.fft.load(|n| {
+            if n < WINDOW_SIZE {
+                // Hann window; twiddle(4 n) = exp(-2 pi i n / WINDOW_SIZE)
+                let w = 0.5 - 0.5 * twiddle(4 * n).re;
+                samples[(pos + n) % WINDOW_SIZE].scale(w)
+            } else {
+                Complex::default()
+            }
+        });
+
+        // audio bin j is at baseband bin j - SPECTRUM_SIZE / 2
+        for (j, x) in out.iter_mut().enumerate() {
+            let k = (j + FFT_SIZE - SPECTRUM_SIZE / 2) % FFT_SIZE;
+            *x = self.fft.bin(k).norm() * SCALE;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::{encode_text, message::Message},
+        testutil::{synth_iq, Rng},
+    };
+    use std::{collections::BTreeMap, vec::Vec};
+
+    #[test]
+    fn test_rates() {
+        for rate in [
+            4000, 8000, 12000, 48000, 96000, 192000, 2_048_000, 2_400_000,
+        ] {
+            assert!(IqSpectrogram::new(rate, 0.0).is_some(), "{}", rate);
+        }
+        for rate in [0, 1000, 11025, 28000] {
+            assert!(IqSpectrogram::new(rate, 0.0).is_none(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn test_scaling() {
+        // 0.5 amplitude tone at 1000 Hz above the offset, and a strong one in the other sideband
+        for rate in [48000, 2_048_000] {
+            let offset = -7000.0;
+            let samples = (0..rate / 2)
+                .map(|i| {
+                    let t = i as f64 / rate as f64;
+                    let a = (2.0 * core::f64::consts::PI * (offset + 1000.0) * t) as f32;
+                    let b = (2.0 * core::f64::consts::PI * (offset - 1000.0) * t) as f32;
+                    [0.5 * a.cos() + b.cos(), 0.5 * a.sin() + b.sin()]
+                })
+                .collect::<Vec<_>>();
+
+            let mut s = IqSpectrogram::new(rate, offset as f32).unwrap();
+            let mut last = [0.0; SPECTRUM_SIZE];
+            s.put_samples_with(&samples, |x| last = *x);
+
+            let peak = (0..SPECTRUM_SIZE)
+                .max_by(|&a, &b| last[a].total_cmp(&last[b]))
+                .unwrap();
+            assert_eq!(peak, 320, "{}", rate);
+            assert!((last[320] - 0.5).abs() < 1e-2, "{}: {}", rate, last[320]);
+        }
+    }
+
+    #[test]
+    fn test_decode_sub_bands() {
+        let rate = 16000;
+        let texts: [&[u8]; 2] = [b"CQ JA1ZLO PM95", b"JA1ZLO JJ1FYD -10"];
+        let offsets = [-6000.0, 2000.0];
+
+        // two sub-bands in one capture
+        let mut rng = Rng(5);
+        let mut iq = vec![[0.0f32; 2]; rate as usize * 15];
+        for (text, offset) in texts.iter().zip(offsets) {
+            let tones = encode_text(text, None::<&mut ()>).unwrap();
+            let sig = synth_iq(&tones, rate, offset + 1000.0, 0.51, 0.1, 0.2, &mut rng);
+            for (x, y) in iq.iter_mut().zip(sig.iter()) {
+                x[0] += y[0];
+                x[1] += y[1];
+            }
+        }
+
+        for (text, offset) in texts.iter().zip(offsets) {
+            let mut s = IqSpectrogram::new(rate, offset).unwrap();
+            let mut decoder = Decoder::<f32, f32>::new();
+            s.put_samples(&iq, &mut decoder);
+
+            let mut hashtable = BTreeMap::new();
+            let decodes = decoder.decode_all(&mut hashtable).collect::<Vec<_>>();
+            assert_eq!(decodes.len(), 1);
+            let d = &decodes[0];
+            assert_eq!(d.freq, 1000.0);
+            assert!((d.dt - 0.51).abs() <= 0.04, "{}", d.dt);
+            assert_eq!(
+                Some(&d.message),
+                Message::from_text(text, None::<&mut ()>).as_ref()
+            );
+        }
+    }
+}
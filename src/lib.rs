@@ -1,13 +1,19 @@
 #![cfg_attr(feature = "no_std", no_std)]
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs, inherent_associated_types)]
 
+pub mod coherent;
 pub mod decoder;
 pub mod protocol;
+pub mod resample;
+pub mod spectrogram;
 
 mod bits;
-pub use bits::Bitset;
+pub use bits::{num_words, Bitset};
 
 pub mod float;
 
+pub mod iq;
+
 pub mod util;
+
+#[cfg(test)]
+mod testutil;
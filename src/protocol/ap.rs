@@ -0,0 +1,243 @@
+// a-priori (AP) decoding
+// bits of the next message that are known in advance (own call, DX call, reply type)
+// are forced to strong LLRs before solving, which lets weaker signals decode.
+// AP decodes have a higher false-decode rate, so results are labeled with the AP type used.
+
+use super::{
+    crc::check_crc, ldpc, message::callsign::C28, message::G15, MessageBits, MessageBitsWithCRC,
+    BODY_BITS, PAYLOAD_BITS,
+};
+use crate::float::FloatS;
+
+// i3 value of standard messages
+const I3_STD: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApType {
+    // no a-priori information
+    None,
+    // CQ ??? ???
+    Cq,
+    // MyCall ??? ???
+    MyCall,
+    // MyCall DxCall ???
+    MyCallDxCall,
+    // MyCall DxCall RRR
+    MyCallDxCallRrr,
+    // MyCall DxCall RR73
+    MyCallDxCallRr73,
+    // MyCall DxCall 73
+    MyCallDxCall73,
+}
+
+impl ApType {
+    pub const ALL: [ApType; 7] = [
+        ApType::None,
+        ApType::Cq,
+        ApType::MyCall,
+        ApType::MyCallDxCall,
+        ApType::MyCallDxCallRrr,
+        ApType::MyCallDxCallRr73,
+        ApType::MyCallDxCall73,
+    ];
+
+    // WSJT-X style label
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApType::None => "",
+            ApType::Cq => "a1",
+            ApType::MyCall => "a2",
+            ApType::MyCallDxCall => "a3",
+            ApType::MyCallDxCallRrr => "a4",
+            ApType::MyCallDxCallRr73 => "a5",
+            ApType::MyCallDxCall73 => "a6",
+        }
+    }
+}
+
+// set of known message bits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApMask {
+    pub mask: MessageBits,
+    pub value: MessageBits,
+}
+
+impl ApMask {
+    pub fn set_slice(&mut self, start: usize, size: usize, value: u32) {
+        self.mask.set_slice(start, size, u32::MAX);
+        self.value.set_slice(start, size, value);
+    }
+
+    pub fn set(&mut self, i: usize, value: bool) {
+        self.mask.set(i, true);
+        self.value.set(i, value);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    // whether decoded bits agree with the known bits
+    pub fn matches(&self, bits: &MessageBitsWithCRC) -> bool {
+        (0..BODY_BITS).all(|i| !self.mask.get(i) || self.value.get(i) == bits.get(i))
+    }
+
+    // overwrite LLRs of the known bits with strong values
+    // the magnitude is taken slightly above the strongest received bit, so that the scale of LLRs does not matter
+    pub fn apply<F: FloatS>(&self, data: &mut [F]) {
+        debug_assert!(data.len() == PAYLOAD_BITS);
+        let mag = data
+            .iter()
+            .map(|&x| Into::<f32>::into(x).abs())
+            .fold(0.0f32, f32::max)
+            * 1.01;
+        let mag = if mag > 0.0 { mag } else { 1.0 };
+        for (i, x) in data.iter_mut().enumerate().take(BODY_BITS) {
+            if self.mask.get(i) {
+                *x = (if self.value.get(i) { mag } else { -mag }).into();
+            }
+        }
+    }
+}
+
+// what we know about the current QSO
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApContext {
+    pub my_call: Option<C28>,
+    pub dx_call: Option<C28>,
+}
+
+impl ApContext {
+    pub fn new(my_call: Option<C28>, dx_call: Option<C28>) -> Self {
+        Self { my_call, dx_call }
+    }
+
+    // known bits for given AP type; None if the context lacks the needed callsigns
+    pub fn mask(&self, ty: ApType) -> Option<ApMask> {
+        let mut m = ApMask::default();
+        let call1 = match ty {
+            ApType::None => return Some(m),
+            ApType::Cq => C28::CQ,
+            _ => self.my_call?,
+        };
+        m.set_slice(0, 28, call1.0);
+        m.set(28, false);
+        m.set_slice(74, 3, I3_STD);
+
+        if ty >= ApType::MyCallDxCall {
+            m.set_slice(29, 28, self.dx_call?.0);
+            m.set(57, false);
+        }
+
+        let grid = match ty {
+            ApType::MyCallDxCallRrr => G15::RRR,
+            ApType::MyCallDxCallRr73 => G15::RR73,
+            ApType::MyCallDxCall73 => G15::V73,
+            _ => return Some(m),
+        };
+        m.set(58, false);
+        m.set_slice(59, 15, grid.0 as u32);
+        Some(m)
+    }
+}
+
+// solve with each AP type in turn, returning the first one with valid CRC
+// out: the message bits, the number of errors, and the AP type used
+pub fn solve_ap<F: FloatS>(
+    data: &[F],
+    ctx: &ApContext,
+    types: &[ApType],
+) -> Option<(MessageBitsWithCRC, u8, ApType)> {
+    debug_assert!(data.len() == PAYLOAD_BITS);
+
+    for &ty in types {
+        let Some(mask) = ctx.mask(ty) else {
+            continue;
+        };
+
+        let mut llr = [0.0f32; PAYLOAD_BITS];
+        for (x, &y) in llr.iter_mut().zip(data.iter()) {
+            *x = y.into();
+        }
+        mask.apply(&mut llr);
+
+        let (bits, err) = ldpc::solve(&llr);
+        if err != 0 || !check_crc(&bits) || bits.0.iter().all(|&x| x == 0) {
+            continue;
+        }
+        if !mask.matches(&bits) {
+            continue;
+        }
+        return Some((bits, err, ty));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{crc::add_crc, message::Message};
+
+    fn context() -> ApContext {
+        ApContext::new(C28::from_call(b"JA1ZLO"), C28::from_call(b"JJ1FYD"))
+    }
+
+    #[test]
+    fn test_ap_mask() {
+        let ctx = context();
+        assert_eq!(ctx.mask(ApType::None).unwrap().count(), 0);
+        assert_eq!(ctx.mask(ApType::Cq).unwrap().count(), 32);
+        assert_eq!(ctx.mask(ApType::MyCall).unwrap().count(), 32);
+        assert_eq!(ctx.mask(ApType::MyCallDxCall).unwrap().count(), 61);
+        assert_eq!(ctx.mask(ApType::MyCallDxCallRr73).unwrap().count(), 77);
+
+        let ctx = ApContext::new(C28::from_call(b"JA1ZLO"), None);
+        assert!(ctx.mask(ApType::MyCall).is_some());
+        assert!(ctx.mask(ApType::MyCallDxCall).is_none());
+
+        // fully known message equals the encoded one
+        let msg = Message::StdMsg {
+            call1: C28::from_call(b"JA1ZLO").unwrap(),
+            call1_r: false,
+            call2: C28::from_call(b"JJ1FYD").unwrap(),
+            call2_r: false,
+            r: false,
+            grid: G15::RR73,
+        };
+        assert_eq!(
+            context().mask(ApType::MyCallDxCallRr73).unwrap().value,
+            msg.encode()
+        );
+    }
+
+    #[test]
+    fn test_solve_ap() {
+        let msg = Message::StdMsg {
+            call1: C28::from_call(b"JA1ZLO").unwrap(),
+            call1_r: false,
+            call2: C28::from_call(b"JJ1FYD").unwrap(),
+            call2_r: false,
+            r: true,
+            grid: G15::from_report(-15),
+        };
+        let bits = add_crc(msg.encode());
+        let cw = ldpc::encode(&bits);
+
+        // callsign bits are received mostly wrong
+        let mut llr = [0.0f32; PAYLOAD_BITS];
+        for (i, x) in llr.iter_mut().enumerate() {
+            let v = if cw.get(i) { 3.0 } else { -3.0 };
+            *x = if i < 57 && i % 3 != 0 { -v } else { v };
+        }
+
+        assert!(solve_ap(&llr, &context(), &[ApType::None]).is_none());
+
+        let (out, _, ty) = solve_ap(&llr, &context(), &ApType::ALL).unwrap();
+        assert_eq!(out, bits);
+        assert_eq!(ty, ApType::MyCallDxCall);
+
+        // wrong DX call does not help
+        let ctx = ApContext::new(C28::from_call(b"JA1ZLO"), C28::from_call(b"K1ABC"));
+        assert!(solve_ap(&llr, &ctx, &[ApType::MyCallDxCall]).is_none());
+    }
+}
@@ -1,32 +1,202 @@
-use super::{MessageBits, MessageBitsWithCRC, BODY_BITS, CRC_BITS};
+use super::{FullMessageBits, MessageBits, MessageBitsWithCRC, BODY_BITS, CRC_BITS};
 
 // pub const POLY: u16 = 0x6757;
 pub const POLY: u16 = 0x6757;
 
+const CRC_MASK: u16 = (1 << CRC_BITS) - 1;
+
+// the body is zero-padded to 82 bits before the CRC is taken
+const PAD_BITS: usize = 5;
+
+// TABLE[v] = v(x) * x^14 mod POLY
+const TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << (CRC_BITS - 8);
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & (1 << (CRC_BITS - 1)) != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+            j += 1;
+        }
+        table[i] = crc & CRC_MASK;
+        i += 1;
+    }
+    table
+};
+
+// streaming CRC-14 calculator; bits are fed MSB first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc14(u16);
+
+impl Crc14 {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    #[inline]
+    pub fn update_byte(&mut self, byte: u8) {
+        self.update_bits8(byte, 8);
+    }
+
+    // feed lower `size` bits of `value`
+    #[inline]
+    pub fn update_bits(&mut self, value: u32, size: usize) {
+        debug_assert!(size <= 32);
+        let mut rem = size;
+        while rem >= 8 {
+            rem -= 8;
+            self.update_bits8((value >> rem) as u8, 8);
+        }
+        if rem > 0 {
+            self.update_bits8((value & ((1 << rem) - 1)) as u8, rem);
+        }
+    }
+
+    #[inline]
+    pub fn update_word(&mut self, word: u32) {
+        self.update_bits(word, 32);
+    }
+
+    // feed the first `size` bits of a bitset
+    pub fn update_bitset<const SIZE: usize, const WORDS: usize>(
+        &mut self,
+        data: &crate::Bitset<SIZE, WORDS>,
+        size: usize,
+    ) {
+        debug_assert!(size <= SIZE);
+        for (i, &w) in data.0.iter().enumerate() {
+            let n = size.saturating_sub(i * 32).min(32);
+            if n == 0 {
+                break;
+            }
+            self.update_bits(w >> (32 - n), n);
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+
+    // k <= 8
+    #[inline]
+    fn update_bits8(&mut self, value: u8, k: usize) {
+        let top = (self.0 >> (CRC_BITS - k)) as u8 ^ value;
+        self.0 = ((self.0 << k) & CRC_MASK) ^ TABLE[top as usize];
+    }
+}
+
 // message and CRC are given as a bitset
 pub fn check_crc(data: &MessageBitsWithCRC) -> bool {
-    calc_crc(&data.with_size()) == data.slice(BODY_BITS, CRC_BITS) as u16
+    body_crc(&data.0) == data.slice(BODY_BITS, CRC_BITS) as u16
 }
 
-pub fn calc_crc(data: &MessageBits) -> u16 {
-    let mut crc = 0u32;
-    // TODO: speed up
-    for i in 0..95 {
-        crc ^= if i < BODY_BITS && data.get(i) { 1 } else { 0 };
-        if crc & (1 << 13) != 0 {
-            crc = (crc << 1) ^ POLY as u32;
-        } else {
-            crc <<= 1;
-        }
-    }
+// check CRC on the whole codeword; parity bits are ignored
+pub fn check_crc_full(data: &FullMessageBits) -> bool {
+    body_crc(&data.0) == data.slice(BODY_BITS, CRC_BITS) as u16
+}
 
-    (crc & ((1 << CRC_BITS) - 1)) as u16
+pub fn calc_crc(data: &MessageBits) -> u16 {
+    body_crc(&data.0)
 }
 
 pub fn add_crc(data: MessageBits) -> MessageBitsWithCRC {
-    // let mut ret = data.with_size();
-    let mut ret = unsafe { core::mem::transmute::<MessageBits, MessageBitsWithCRC>(data) };
+    let mut ret: MessageBitsWithCRC = data.with_size();
     let crc = calc_crc(&data);
     ret.set_slice(BODY_BITS, CRC_BITS, crc as u32);
     ret
 }
+
+// CRC of the first BODY_BITS bits of given words
+#[inline]
+fn body_crc(words: &[u32]) -> u16 {
+    let mut crc = Crc14::new();
+    crc.update_word(words[0]);
+    crc.update_word(words[1]);
+    // 13 remaining body bits followed by zero padding
+    let n = BODY_BITS - 64;
+    crc.update_bits((words[2] >> (32 - n)) << PAD_BITS, n + PAD_BITS);
+    crc.value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reference bit-serial implementation
+    fn calc_crc_serial(data: &MessageBits) -> u16 {
+        let mut crc = 0u32;
+        for i in 0..95 {
+            crc ^= if i < BODY_BITS && data.get(i) { 1 } else { 0 };
+            if crc & (1 << 13) != 0 {
+                crc = (crc << 1) ^ POLY as u32;
+            } else {
+                crc <<= 1;
+            }
+        }
+
+        (crc & ((1 << CRC_BITS) - 1)) as u16
+    }
+
+    #[test]
+    fn test_crc_table() {
+        let mut x = 0x12345678u32;
+        let mut next = || {
+            // xorshift32
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x
+        };
+        for _ in 0..1000 {
+            let bs = MessageBits::from([next(), next(), next()]);
+            let bs: MessageBits = bs.with_size::<91, 3>().with_size(); // clear unused bits
+            assert_eq!(calc_crc(&bs), calc_crc_serial(&bs));
+        }
+
+        for i in 0..BODY_BITS {
+            let mut bs = MessageBits::default();
+            bs.set(i, true);
+            assert_eq!(calc_crc(&bs), calc_crc_serial(&bs));
+        }
+    }
+
+    #[test]
+    fn test_crc_check() {
+        let mut bs = MessageBits::default();
+        for i in (0..BODY_BITS).step_by(3) {
+            bs.set(i, true);
+        }
+        let bs = add_crc(bs);
+        assert!(check_crc(&bs));
+        assert!(check_crc_full(&bs.with_size()));
+
+        let mut broken = bs;
+        broken.set(10, !broken.get(10));
+        assert!(!check_crc(&broken));
+        assert!(!check_crc_full(&broken.with_size()));
+    }
+
+    #[test]
+    fn test_crc_streaming() {
+        let bs = MessageBits::from([0xdeadbeef, 0x01234567, 0x89a80000]);
+
+        let mut crc = Crc14::new();
+        crc.update_bitset(&bs, BODY_BITS);
+        crc.update_bits(0, PAD_BITS);
+        assert_eq!(crc.value(), calc_crc_serial(&bs));
+
+        // bit by bit
+        let mut crc = Crc14::new();
+        for i in 0..BODY_BITS {
+            crc.update_bits(bs.get(i) as u32, 1);
+        }
+        crc.update_bits(0, PAD_BITS);
+        assert_eq!(crc.value(), calc_crc_serial(&bs));
+    }
+}
@@ -0,0 +1,208 @@
+use super::{
+    check, C_SIZE, EOL, MAX_ITER, MAX_ITER_NO_PROGRESS, TABLE_CV, TABLE_CV_LEN, TABLE_VC,
+    TABLE_VC_LEN, V_SIZE,
+};
+use crate::{
+    float::FixedS,
+    protocol::{FullMessageBits, MessageBitsWithCRC},
+};
+
+// messages are saturated to this magnitude
+const MSG_MAX: i16 = 0x7ff;
+
+// normalization factor of check-to-variable messages: 3/4
+#[inline]
+fn normalize(x: i16) -> i16 {
+    x - (x >> 2)
+}
+
+#[inline]
+fn sat(x: i32) -> i16 {
+    x.clamp(-MSG_MAX as i32, MSG_MAX as i32) as i16
+}
+
+// normalized min-sum decoder working on fixed-point LLRs
+// same interface as `solve`; no floating point operations are involved
+// out: the message bits
+// returns the number of errors
+pub fn solve_min_sum<F: FixedS>(message: &[F]) -> (MessageBitsWithCRC, u8) {
+    debug_assert!(message.len() == V_SIZE);
+
+    // NOTE: messages are kept in log(P0/P1) domain, i.e. negated input
+    let mut llr = [0i16; V_SIZE];
+    for (i, &b) in message.iter().enumerate() {
+        llr[i] = -b.raw();
+    }
+
+    let mut plain = FullMessageBits::default();
+
+    let mut tov = [[0i16; TABLE_VC_LEN]; V_SIZE];
+    let mut toc = [[0i16; TABLE_CV_LEN]; C_SIZE];
+
+    let mut min_err = C_SIZE as u8;
+    let mut last_err = min_err;
+    let mut count_no_progress = 0;
+
+    for _ in 0..MAX_ITER {
+        // check
+
+        for i in 0..V_SIZE {
+            let sum = llr[i] as i32 + tov[i].iter().map(|&x| x as i32).sum::<i32>();
+            plain.set(i, sum < 0);
+        }
+
+        last_err = check(&plain);
+
+        if last_err == 0 {
+            break;
+        }
+        if last_err < min_err {
+            min_err = last_err;
+            count_no_progress = 0;
+        } else {
+            count_no_progress += 1;
+            if count_no_progress >= MAX_ITER_NO_PROGRESS {
+                break;
+            }
+        }
+
+        // improvement iteration
+
+        for (m, row) in TABLE_CV.iter().enumerate() {
+            for (i, &n) in row.iter().enumerate() {
+                if n == EOL {
+                    break;
+                }
+                let mut sum = llr[n as usize] as i32;
+                for (j, &m1) in TABLE_VC[n as usize].iter().enumerate() {
+                    if m1 != m as u8 {
+                        sum += tov[n as usize][j] as i32;
+                    }
+                }
+                toc[m][i] = sat(sum);
+            }
+        }
+
+        for (m, row) in TABLE_CV.iter().enumerate() {
+            // two smallest magnitudes and the overall sign
+            let mut min1 = MSG_MAX;
+            let mut min2 = MSG_MAX;
+            let mut min_idx = 0;
+            let mut neg = false;
+            for (j, &n) in row.iter().enumerate() {
+                if n == EOL {
+                    break;
+                }
+                let v = toc[m][j];
+                neg ^= v < 0;
+                let a = v.abs();
+                if a < min1 {
+                    min2 = min1;
+                    min1 = a;
+                    min_idx = j;
+                } else if a < min2 {
+                    min2 = a;
+                }
+            }
+
+            for (j, &n) in row.iter().enumerate() {
+                if n == EOL {
+                    break;
+                }
+                let mag = normalize(if j == min_idx { min2 } else { min1 });
+                let v = if neg ^ (toc[m][j] < 0) { -mag } else { mag };
+                let k = TABLE_VC[n as usize]
+                    .iter()
+                    .position(|&m1| m1 as usize == m)
+                    .unwrap();
+                tov[n as usize][k] = v;
+            }
+        }
+    }
+    (plain.with_size(), last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        float::Fixed8,
+        protocol::{crc::add_crc, ldpc::encode, ldpc::solve, MessageBits},
+    };
+
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            // xorshift64
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 32) as u32
+        }
+        fn uniform(&mut self) -> f32 {
+            (self.next() as f32 + 1.0) / (u32::MAX as f32 + 2.0)
+        }
+        fn gauss(&mut self) -> f32 {
+            // Box-Muller
+            let (u, v) = (self.uniform(), self.uniform());
+            (-2.0 * u.ln()).sqrt() * (2.0 * core::f32::consts::PI * v).cos()
+        }
+    }
+
+    // returns (float successes, min-sum successes)
+    fn run(ebn0_db: f32, frames: usize) -> (usize, usize) {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let rate = 91.0 / 174.0;
+        let sigma = (1.0 / (2.0 * rate * 10f32.powf(ebn0_db / 10.0))).sqrt();
+
+        let mut ok = (0, 0);
+        for _ in 0..frames {
+            let msg = MessageBits::from([rng.next(), rng.next(), rng.next() & !0x7ffff]);
+            let msg = add_crc(msg);
+            let cw = encode(&msg);
+
+            let mut llr = [0.0f32; V_SIZE];
+            for (i, x) in llr.iter_mut().enumerate() {
+                let y = if cw.get(i) { 1.0 } else { -1.0 } + sigma * rng.gauss();
+                *x = 2.0 * y / (sigma * sigma);
+            }
+            let llr_fixed = llr.map(Fixed8::from);
+
+            if solve(&llr).0 == msg {
+                ok.0 += 1;
+            }
+            if solve_min_sum(&llr_fixed).0 == msg {
+                ok.1 += 1;
+            }
+        }
+        ok
+    }
+
+    #[test]
+    fn test_min_sum_noiseless() {
+        let msg = add_crc(MessageBits::from([0x12345678, 0x9abcdef0, 0x12340000]));
+        let cw = encode(&msg);
+        let llr = (0..V_SIZE)
+            .map(|i| Fixed8::from(if cw.get(i) { 2.0 } else { -2.0 }))
+            .collect::<Vec<_>>();
+        assert_eq!(solve_min_sum(&llr), (msg, 0));
+    }
+
+    #[test]
+    fn test_min_sum_vs_float() {
+        // success rate must stay close to the float decoder
+        for (ebn0, frames) in [(2.0, 100), (3.0, 100)] {
+            let (f, m) = run(ebn0, frames);
+            assert!(f > 0, "Eb/N0 {:.1} dB: float {}/{}", ebn0, f, frames);
+            assert!(
+                m * 10 >= f * 8,
+                "Eb/N0 {:.1} dB: min-sum {}/{} vs float {}/{}",
+                ebn0,
+                m,
+                frames,
+                f,
+                frames
+            );
+        }
+    }
+}
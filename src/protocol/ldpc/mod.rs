@@ -1,20 +1,6 @@
 use super::{FullMessageBits, MessageBitsWithCRC, BODY_BITS, CRC_BITS, PAYLOAD_BITS};
 use crate::float::FloatS;
 
-#[cfg(feature = "no_std")]
-use micromath::F32Ext;
-
-// TODO: speed up if needed
-fn tanh(x: f32) -> f32 {
-    let a = x.exp();
-    let b = 1.0 / a;
-    (a - b) / (a + b)
-}
-
-fn atanh(x: f32) -> f32 {
-    0.5 * ((1.0 + x) / (1.0 - x)).ln()
-}
-
 const V_SIZE: usize = PAYLOAD_BITS;
 const C_SIZE: usize = 83;
 const MSG_BITS: usize = BODY_BITS + CRC_BITS;
@@ -22,6 +8,17 @@ const MSG_BITS: usize = BODY_BITS + CRC_BITS;
 const MAX_ITER: usize = 100;
 const MAX_ITER_NO_PROGRESS: usize = 10;
 
+mod solver;
+pub use solver::{SolveResult, Solver, SolverStatus};
+
+mod minsum;
+pub use minsum::solve_min_sum;
+
+#[cfg(feature = "osd")]
+mod osd;
+#[cfg(feature = "osd")]
+pub use osd::solve_osd;
+
 pub fn check(message: &FullMessageBits) -> u8 {
     let mut count = 0;
     for row in TABLE_CV.iter() {
@@ -39,88 +36,27 @@ pub fn check(message: &FullMessageBits) -> u8 {
     count
 }
 
-// NOTE: original solve algorithm from kgoba/ft8_lib Copyright (c) 2018 Kārlis Goba
 // solve the parity check equations
 // out: the message bits
 // returns the number of errors
 pub fn solve<F: FloatS>(message: &[F]) -> (MessageBitsWithCRC, u8) {
-    debug_assert!(message.len() == V_SIZE);
-
-    let mut message_f32 = [0.0f32; V_SIZE];
-    for (i, &b) in message.iter().enumerate() {
-        message_f32[i] = b.into();
-    }
-
-    let mut plain = FullMessageBits::default();
-
-    let mut tov = [[0.0f32; TABLE_VC_LEN]; V_SIZE];
-    let mut toc = [[0.0f32; TABLE_CV_LEN]; C_SIZE];
-
-    let mut min_err = C_SIZE as u8;
-    let mut last_err = min_err;
-    let mut count_no_progress = 0;
-
-    for _ in 0..MAX_ITER {
-        // check
-
-        for i in 0..V_SIZE {
-            plain.set(i, (message_f32[i] + tov[i].iter().sum::<f32>()) > 0.0);
-        }
-
-        last_err = check(&plain);
-
-        if last_err == 0 {
-            break;
-        }
-        if last_err < min_err {
-            min_err = last_err;
-            count_no_progress = 0;
-        } else {
-            count_no_progress += 1;
-            if count_no_progress >= MAX_ITER_NO_PROGRESS {
-                break;
-            }
-        }
-
-        // improvement iteration
-
-        for (m, row) in TABLE_CV.iter().enumerate() {
-            for (i, &n) in row.iter().enumerate() {
-                if n == EOL {
-                    break;
-                }
-                let mut sum = message_f32[n as usize];
-                for (j, &m1) in TABLE_VC[n as usize].iter().enumerate() {
-                    if m1 != m as u8 {
-                        sum += tov[n as usize][j];
-                    }
-                }
-                toc[m][i] = tanh(sum / -2.);
-            }
-        }
+    let mut solver = Solver::new();
+    solver.load(message);
+    solver.step(MAX_ITER);
+    solver.result()
+}
 
-        for (n, row) in TABLE_VC.iter().enumerate() {
-            for (i, &m) in row.iter().enumerate() {
-                let mut sum = 1.0;
-                for (j, &n1) in TABLE_CV[m as usize].iter().enumerate() {
-                    if n1 == EOL {
-                        break;
-                    }
-                    if n1 != n as u8 {
-                        sum *= toc[m as usize][j];
-                    }
-                }
-                tov[n][i] = -2. * atanh(sum);
-            }
-        }
-    }
-    (plain.with_size(), last_err)
+// same as `solve`, but with soft output and convergence diagnostics
+pub fn solve_soft<F: FloatS>(message: &[F]) -> SolveResult {
+    let mut solver = Solver::new();
+    solver.load(message);
+    solver.solve_soft()
 }
 
 pub fn encode(msg: &MessageBitsWithCRC) -> FullMessageBits {
     let mut out = msg.with_size();
 
-    for i in 0..MSG_BITS {
+    for (i, gen) in TABLE_GEN.iter().enumerate() {
         if !msg.get(i) {
             continue;
         }
@@ -128,7 +64,7 @@ pub fn encode(msg: &MessageBitsWithCRC) -> FullMessageBits {
 
         // FEC part
         let mut buf = [0u32; 4];
-        for (j, r) in TABLE_GEN[i].iter().enumerate() {
+        for (j, r) in gen.iter().enumerate() {
             buf[j] |= r >> 27;
             buf[j + 1] |= r << 5;
         }
@@ -411,7 +347,7 @@ pub const TABLE_VC: [[u8; 3]; V_SIZE] = [
 
 #[expect(clippy::unusual_byte_groupings)]
 #[rustfmt::skip]
-const TABLE_GEN: [[u32; (C_SIZE + 31) / 32]; MSG_BITS] = [
+const TABLE_GEN: [[u32; C_SIZE.div_ceil(32)]; MSG_BITS] = [
     [0b10100000101001010000100011011000, 0b11000111001000000010100101111110, 0b1000011101010111000_0000000000000],
     [0b01100001110011110001000100010100, 0b00010111100110010100110010011011, 0b0001100110110101101_0000000000000],
     [0b01000011111011100010000001100101, 0b01101011001000101110111111100010, 0b0110101111010110111_0000000000000],
@@ -0,0 +1,209 @@
+use super::{encode, MSG_BITS, V_SIZE};
+use crate::{
+    float::FloatS,
+    protocol::{crc::check_crc_full, FullMessageBits, MessageBitsWithCRC},
+};
+
+// ordered-statistics decoding
+// the 91 most reliable (and independent) bits are taken as the information set,
+// and codewords obtained by flipping up to `depth` of them are tested.
+// only codewords with valid CRC are accepted.
+// out: the message bits, and the number of hard-decision bits that were corrected
+pub fn solve_osd<F: FloatS>(message: &[F], depth: usize) -> Option<(MessageBitsWithCRC, u8)> {
+    debug_assert!(message.len() == V_SIZE);
+
+    let mut llr = [0.0f32; V_SIZE];
+    for (i, &b) in message.iter().enumerate() {
+        llr[i] = b.into();
+    }
+
+    // bit positions, most reliable first
+    let mut perm = [0u8; V_SIZE];
+    for (i, p) in perm.iter_mut().enumerate() {
+        *p = i as u8;
+    }
+    perm.sort_unstable_by(|&a, &b| {
+        llr[b as usize]
+            .abs()
+            .partial_cmp(&llr[a as usize].abs())
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+
+    // generator matrix with permuted columns
+    let mut gen = [FullMessageBits::default(); MSG_BITS];
+    for (i, row) in gen.iter_mut().enumerate() {
+        let mut msg = MessageBitsWithCRC::default();
+        msg.set(i, true);
+        let cw = encode(&msg);
+        for (k, &p) in perm.iter().enumerate() {
+            row.set(k, cw.get(p as usize));
+        }
+    }
+
+    if !reduce(&mut gen, &mut perm) {
+        return None;
+    }
+
+    let mut hard = FullMessageBits::default();
+    let mut rel = [0.0f32; V_SIZE];
+    for (k, &p) in perm.iter().enumerate() {
+        hard.set(k, llr[p as usize] > 0.0);
+        rel[k] = llr[p as usize].abs();
+    }
+
+    // order-0 codeword
+    let mut c0 = FullMessageBits::default();
+    for (i, row) in gen.iter().enumerate() {
+        if hard.get(i) {
+            c0 ^= *row;
+        }
+    }
+
+    let mut search = Search {
+        gen: &gen,
+        perm: &perm,
+        hard: &hard,
+        rel: &rel,
+        best: None,
+    };
+    search.run(c0, 0, depth);
+
+    search.best.map(|(c, _)| {
+        let mut diff = c;
+        diff ^= hard;
+        let mut out = FullMessageBits::default();
+        for (k, &p) in perm.iter().enumerate() {
+            out.set(p as usize, c.get(k));
+        }
+        (out.with_size(), diff.count_ones() as u8)
+    })
+}
+
+// bring the first MSG_BITS columns into the identity by row operations,
+// swapping in later columns where dependent
+fn reduce(gen: &mut [FullMessageBits; MSG_BITS], perm: &mut [u8; V_SIZE]) -> bool {
+    for col in 0..MSG_BITS {
+        let mut pivot = (col..MSG_BITS).find(|&r| gen[r].get(col));
+
+        if pivot.is_none() {
+            // dependent column; take the next usable one
+            let Some((c2, r)) = (col + 1..V_SIZE)
+                .find_map(|c2| (col..MSG_BITS).find(|&r| gen[r].get(c2)).map(|r| (c2, r)))
+            else {
+                return false;
+            };
+            for row in gen.iter_mut() {
+                let (a, b) = (row.get(col), row.get(c2));
+                row.set(col, b);
+                row.set(c2, a);
+            }
+            perm.swap(col, c2);
+            pivot = Some(r);
+        }
+
+        let pivot = pivot.unwrap();
+        gen.swap(col, pivot);
+        let p = gen[col];
+        for (r, row) in gen.iter_mut().enumerate() {
+            if r != col && row.get(col) {
+                *row ^= p;
+            }
+        }
+    }
+    true
+}
+
+struct Search<'a> {
+    gen: &'a [FullMessageBits; MSG_BITS],
+    perm: &'a [u8; V_SIZE],
+    hard: &'a FullMessageBits,
+    rel: &'a [f32; V_SIZE],
+    best: Option<(FullMessageBits, f32)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, c: FullMessageBits, start: usize, depth: usize) {
+        self.visit(&c);
+        if depth == 0 {
+            return;
+        }
+        for i in start..MSG_BITS {
+            let mut c2 = c;
+            c2 ^= self.gen[i];
+            self.run(c2, i + 1, depth - 1);
+        }
+    }
+
+    fn visit(&mut self, c: &FullMessageBits) {
+        // correlation discrepancy against the hard decision
+        let mut cost = 0.0;
+        for (w, (&x, &y)) in c.0.iter().zip(self.hard.0.iter()).enumerate() {
+            let mut d = x ^ y;
+            while d != 0 {
+                let k = w * 32 + d.leading_zeros() as usize;
+                cost += self.rel[k];
+                d &= !(1 << (31 - (k % 32)));
+            }
+        }
+
+        if self.best.is_some_and(|(_, best)| best <= cost) {
+            return;
+        }
+
+        let mut out = FullMessageBits::default();
+        for (k, &p) in self.perm.iter().enumerate() {
+            out.set(p as usize, c.get(k));
+        }
+        if out.0.iter().all(|&x| x == 0) || !check_crc_full(&out) {
+            return;
+        }
+        self.best = Some((*c, cost));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{crc::add_crc, MessageBits};
+
+    fn codeword() -> (MessageBitsWithCRC, FullMessageBits) {
+        let mut msg = MessageBits::default();
+        for i in (0..77).step_by(5) {
+            msg.set(i, true);
+        }
+        let msg = add_crc(msg);
+        (msg, encode(&msg))
+    }
+
+    #[test]
+    fn test_osd_weak_errors() {
+        let (msg, cw) = codeword();
+        let mut llr = [0.0f32; V_SIZE];
+        for (i, x) in llr.iter_mut().enumerate() {
+            *x = if cw.get(i) { 4.0 } else { -4.0 };
+            // many weak errors
+            if i % 7 == 3 {
+                *x = -*x / 8.0;
+            }
+        }
+        let (bs, err) = solve_osd(&llr, 0).unwrap();
+        assert_eq!(bs, msg);
+        assert_eq!(err as usize, (0..V_SIZE).filter(|i| i % 7 == 3).count());
+    }
+
+    #[test]
+    fn test_osd_strong_error() {
+        let (msg, cw) = codeword();
+        let mut llr = [0.0f32; V_SIZE];
+        for (i, x) in llr.iter_mut().enumerate() {
+            *x = if cw.get(i) { 2.0 } else { -2.0 };
+        }
+        // single confident error; order-0 cannot fix it
+        llr[0] = -llr[0] * 2.0;
+        for i in (1..V_SIZE).step_by(9) {
+            llr[i] = -llr[i] / 4.0;
+        }
+
+        assert_eq!(solve_osd(&llr, 1).map(|x| x.0), Some(msg));
+    }
+}
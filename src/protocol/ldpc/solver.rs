@@ -0,0 +1,373 @@
+use super::{
+    check, C_SIZE, EOL, MAX_ITER, MAX_ITER_NO_PROGRESS, MSG_BITS, TABLE_CV, TABLE_CV_LEN, TABLE_VC,
+    TABLE_VC_LEN, V_SIZE,
+};
+use crate::{
+    float::FloatS,
+    protocol::{FullMessageBits, MessageBitsWithCRC},
+    Bitset,
+};
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+// TODO: speed up if needed
+fn tanh(x: f32) -> f32 {
+    // exp overflows past 88; tanh is 1 in f32 well before 20
+    let a = x.clamp(-20.0, 20.0).exp();
+    let b = 1.0 / a;
+    (a - b) / (a + b)
+}
+
+fn atanh(x: f32) -> f32 {
+    // finite at +-1, which tanh reaches in f32, so that opposite messages cannot cancel to NaN
+    let x = x.clamp(-0.999_999, 0.999_999);
+    0.5 * ((1.0 + x) / (1.0 - x)).ln()
+}
+
+// detailed output of `solve_soft`
+#[derive(Debug, Clone)]
+pub struct SolveResult {
+    pub bits: MessageBitsWithCRC,
+    // number of unsatisfied checks of `bits`
+    pub errors: u8,
+    // number of iterations run
+    pub iterations: usize,
+    // fewest unsatisfied checks seen up to each iteration; valid for `..iterations`
+    pub best_errors: [u8; MAX_ITER],
+    // posterior LLR of each bit
+    pub llr: [f32; V_SIZE],
+    // sum of |LLR| on the message bits
+    pub reliability: f32,
+}
+
+impl SolveResult {
+    #[inline]
+    pub fn converged(&self) -> bool {
+        self.errors == 0
+    }
+
+    #[inline]
+    pub fn best_errors(&self) -> &[u8] {
+        &self.best_errors[..self.iterations]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverStatus {
+    // not converged yet; call `step` again
+    Running { iterations: usize, errors: u8 },
+    // finished; the message bits and the number of errors
+    Done(MessageBitsWithCRC, u8),
+}
+
+// resumable belief propagation solver
+// all the state lives in this object, so that it can be placed in a static buffer
+// and the decoding can be split into small time slices
+#[derive(Clone)]
+pub struct Solver {
+    message: [f32; V_SIZE],
+    tov: [[f32; TABLE_VC_LEN]; V_SIZE],
+    toc: [[f32; TABLE_CV_LEN]; C_SIZE],
+
+    plain: FullMessageBits,
+    iterations: usize,
+    // fewest unsatisfied checks seen up to each iteration
+    best_errors: [u8; MAX_ITER],
+    min_err: u8,
+    last_err: u8,
+    count_no_progress: usize,
+    done: bool,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    // empty solver; `load` a message before stepping
+    pub const fn new() -> Self {
+        Self {
+            message: [0.0; V_SIZE],
+            tov: [[0.0; TABLE_VC_LEN]; V_SIZE],
+            toc: [[0.0; TABLE_CV_LEN]; C_SIZE],
+            plain: Bitset([0; FullMessageBits::LEN]),
+            iterations: 0,
+            best_errors: [0; MAX_ITER],
+            min_err: C_SIZE as u8,
+            last_err: C_SIZE as u8,
+            count_no_progress: 0,
+            done: true,
+        }
+    }
+
+    // start solving a new message; previous state is discarded
+    pub fn load<F: FloatS>(&mut self, message: &[F]) {
+        debug_assert!(message.len() == V_SIZE);
+
+        for (i, &b) in message.iter().enumerate() {
+            self.message[i] = b.into();
+        }
+        self.tov = [[0.0; TABLE_VC_LEN]; V_SIZE];
+        self.toc = [[0.0; TABLE_CV_LEN]; C_SIZE];
+        self.plain = FullMessageBits::default();
+        self.iterations = 0;
+        self.best_errors = [0; MAX_ITER];
+        self.min_err = C_SIZE as u8;
+        self.last_err = self.min_err;
+        self.count_no_progress = 0;
+        self.done = false;
+    }
+
+    // run up to `n` iterations
+    pub fn step(&mut self, n: usize) -> SolverStatus {
+        for _ in 0..n {
+            if self.done {
+                break;
+            }
+            self.iterate();
+        }
+        self.status()
+    }
+
+    pub fn status(&self) -> SolverStatus {
+        if self.done {
+            let (bits, err) = self.result();
+            SolverStatus::Done(bits, err)
+        } else {
+            SolverStatus::Running {
+                iterations: self.iterations,
+                errors: self.last_err,
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    #[inline]
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    // current hard decision
+    pub fn result(&self) -> (MessageBitsWithCRC, u8) {
+        (self.plain.with_size(), self.last_err)
+    }
+
+    // current posterior LLR of each bit
+    pub fn posterior(&self, out: &mut [f32]) {
+        debug_assert!(out.len() == V_SIZE);
+        for (i, x) in out.iter_mut().enumerate() {
+            *x = self.message[i] + self.tov[i].iter().sum::<f32>();
+        }
+    }
+
+    // run to completion, with convergence diagnostics that include earlier `step`s
+    pub fn solve_soft(&mut self) -> SolveResult {
+        while !self.done {
+            self.iterate();
+        }
+
+        let mut llr = [0.0; V_SIZE];
+        self.posterior(&mut llr);
+        let reliability = llr[..MSG_BITS].iter().map(|x| x.abs()).sum();
+        let (bits, errors) = self.result();
+        SolveResult {
+            bits,
+            errors,
+            iterations: self.iterations,
+            best_errors: self.best_errors,
+            llr,
+            reliability,
+        }
+    }
+
+    // NOTE: original solve algorithm from kgoba/ft8_lib Copyright (c) 2018 Kārlis Goba
+    fn iterate(&mut self) {
+        let Self {
+            message,
+            tov,
+            toc,
+            plain,
+            ..
+        } = self;
+
+        // check
+
+        for i in 0..V_SIZE {
+            plain.set(i, (message[i] + tov[i].iter().sum::<f32>()) > 0.0);
+        }
+
+        self.last_err = check(plain);
+        self.best_errors[self.iterations] = self.min_err.min(self.last_err);
+        self.iterations += 1;
+        if self.iterations >= MAX_ITER {
+            self.done = true;
+        }
+
+        if self.last_err == 0 {
+            self.done = true;
+            return;
+        }
+        if self.last_err < self.min_err {
+            self.min_err = self.last_err;
+            self.count_no_progress = 0;
+        } else {
+            self.count_no_progress += 1;
+            if self.count_no_progress >= MAX_ITER_NO_PROGRESS {
+                self.done = true;
+                return;
+            }
+        }
+
+        // improvement iteration
+
+        for (m, row) in TABLE_CV.iter().enumerate() {
+            for (i, &n) in row.iter().enumerate() {
+                if n == EOL {
+                    break;
+                }
+                let mut sum = message[n as usize];
+                for (j, &m1) in TABLE_VC[n as usize].iter().enumerate() {
+                    if m1 != m as u8 {
+                        sum += tov[n as usize][j];
+                    }
+                }
+                toc[m][i] = tanh(sum / -2.);
+            }
+        }
+
+        for (n, row) in TABLE_VC.iter().enumerate() {
+            for (i, &m) in row.iter().enumerate() {
+                let mut sum = 1.0;
+                for (j, &n1) in TABLE_CV[m as usize].iter().enumerate() {
+                    if n1 == EOL {
+                        break;
+                    }
+                    if n1 != n as u8 {
+                        sum *= toc[m as usize][j];
+                    }
+                }
+                tov[n][i] = -2. * atanh(sum);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{
+        crc::add_crc,
+        ldpc::{encode, solve, solve_soft},
+        MessageBits,
+    };
+
+    fn noisy() -> (MessageBitsWithCRC, Vec<f32>) {
+        let msg = add_crc(MessageBits::from([0x0badf00d, 0xcafebabe, 0x55550000]));
+        let cw = encode(&msg);
+
+        let mut x = 0x2545f491u32;
+        let llr = (0..V_SIZE)
+            .map(|i| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                let noise = (x % 1000) as f32 / 1000.0 * 7.2 - 3.6;
+                (if cw.get(i) { 3.0 } else { -3.0 }) + noise
+            })
+            .collect::<Vec<_>>();
+        (msg, llr)
+    }
+
+    #[test]
+    fn test_solver_resume() {
+        let (msg, llr) = noisy();
+        let expected = solve(&llr);
+
+        let mut solver = Solver::new();
+        assert!(solver.is_done());
+        solver.load(&llr);
+        let mut steps = 0;
+        let result = loop {
+            match solver.step(1) {
+                SolverStatus::Running { iterations, .. } => {
+                    steps += 1;
+                    assert_eq!(iterations, steps);
+                }
+                SolverStatus::Done(bits, err) => break (bits, err),
+            }
+        };
+        assert!(steps > 0);
+        assert_eq!(result, expected);
+        assert_eq!(result.0, msg);
+
+        // reusable after completion
+        solver.load(&llr);
+        assert_eq!(
+            solver.step(MAX_ITER),
+            SolverStatus::Done(expected.0, expected.1)
+        );
+    }
+
+    #[test]
+    fn test_large_llr() {
+        // a strong signal, past where tanh overflows, with a few weak wrong bits
+        let (msg, _) = noisy();
+        let cw = encode(&msg);
+        let llr = (0..V_SIZE)
+            .map(|i| {
+                let v = if cw.get(i) { 300.0 } else { -300.0 };
+                if i % 40 == 7 {
+                    -v / 100.0
+                } else {
+                    v
+                }
+            })
+            .collect::<Vec<_>>();
+        let (bits, errors) = solve(&llr);
+        assert_eq!(errors, 0);
+        assert_eq!(bits, msg);
+    }
+
+    #[test]
+    fn test_solve_soft() {
+        let (msg, llr) = noisy();
+        let res = solve_soft(&llr);
+
+        assert_eq!((res.bits, res.errors), solve(&llr));
+        assert_eq!(res.bits, msg);
+        assert!(res.converged());
+        assert!(res.iterations > 1);
+
+        let best = res.best_errors();
+        assert_eq!(best.len(), res.iterations);
+        assert!(best.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(best.last(), Some(&0));
+
+        for (i, &x) in res.llr.iter().enumerate().take(MSG_BITS) {
+            assert_eq!(x > 0.0, msg.get(i));
+        }
+        assert!(res.reliability > 0.0);
+    }
+
+    #[test]
+    fn test_solve_soft_resumed() {
+        let (_, llr) = noisy();
+        let full = solve_soft(&llr);
+
+        // iterations run by `step` count in the diagnostics
+        let mut solver = Solver::new();
+        solver.load(&llr);
+        solver.step(2);
+        let res = solver.solve_soft();
+        assert_eq!(res.iterations, full.iterations);
+        assert_eq!(res.best_errors(), full.best_errors());
+        assert!(res.best_errors()[0] > 0);
+    }
+}
@@ -75,6 +75,28 @@ impl C28 {
         Some(Self(val + Self::VALUE_CALLSIGN_RANGE.start()))
     }
 
+    // CQ with a modifier, i.e. "CQ nnn" or "CQ A".."CQ ZZZZ"
+    pub fn from_cq(modifier: &[u8]) -> Option<Self> {
+        let modifier = trim_u8str(modifier);
+        if modifier.len() == 3 && modifier.iter().all(|c| c.is_ascii_digit()) {
+            let num = modifier
+                .iter()
+                .fold(0, |acc, &c| acc * 10 + (c - b'0') as u32);
+            return Some(Self(num + Self::VALUE_CQNUM_RANGE.start()));
+        }
+        if modifier.is_empty() || modifier.len() > 4 {
+            return None;
+        }
+        let mut val = 0;
+        for &c in modifier {
+            match Chars::AlphaSpc.find(c) {
+                Some(x) if x > 0 => val = val * 27 + x as u32,
+                _ => return None,
+            }
+        }
+        Some(Self(val + Self::VALUE_CQZONE_RANGE.start()))
+    }
+
     pub fn from_hash(hash: u32) -> Self {
         Self(hash + Self::VALUE_HASH_RANGE.start())
     }
@@ -164,14 +186,6 @@ impl C28 {
     }
 }
 
-fn alphas_to_num(seq: &[u8]) -> u32 {
-    let mut val = 0;
-    for &c in seq {
-        val = val * 26 + (c - b'A') as u32;
-    }
-    val
-}
-
 #[cfg(not(feature = "no_std"))]
 impl core::fmt::Display for C28 {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
@@ -253,6 +267,9 @@ mod tests {
             let c = C28(*num);
             let n = c.write_str(&mut out, None::<&()>).unwrap();
             assert_eq!(&out[..n], *ret);
+            if *num > C28::VALUE_CQ {
+                assert_eq!(C28::from_cq(&ret[3..]), Some(c));
+            }
         }
     }
 }
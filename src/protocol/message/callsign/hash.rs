@@ -108,8 +108,6 @@ pub trait CallsignHashTable {
 
 impl<const N: usize, const M: usize> CallsignHashTable
     for super::hashtable::HashTable<FullCallsign, N, M>
-where
-    [FullCallsign; super::hashtable::table_size(N)]: Sized,
 {
     fn find_hash(&self, hash: CallsignHash) -> Option<&FullCallsign> {
         self.get_partial(hash.as_h22())
@@ -1,4 +1,5 @@
 const HASH_BITS: usize = 10;
+const BLOCK_SIZE: usize = 1 << HASH_BITS;
 
 pub const fn table_size(n: usize) -> usize {
     n << HASH_BITS
@@ -8,20 +9,15 @@ pub const fn table_size(n: usize) -> usize {
 // capacity = N * 1024
 // M is the max address shift, i.e. the number of entries with the same hash
 // generally, M should be greater than N
+// storage is N blocks of 1024 entries, so that the size is expressible without generic_const_exprs
 #[derive(Debug)]
-pub struct HashTable<T: Sized, const N: usize, const M: usize>
-where
-    [T; table_size(N)]: Sized,
-{
-    table: [Option<HashTableEntry<T>>; table_size(N)],
+pub struct HashTable<T: Sized, const N: usize, const M: usize> {
+    table: [[Option<HashTableEntry<T>>; BLOCK_SIZE]; N],
     count: usize,
     gen: u32,
 }
 
-impl<T: Sized, const N: usize, const M: usize> HashTable<T, N, M>
-where
-    [T; table_size(N)]: Sized,
-{
+impl<T: Sized, const N: usize, const M: usize> HashTable<T, N, M> {
     const _ASSERT: () = {
         assert!(M > N, "M greater than N is useless");
         assert!(N > 0, "N must be greater than 0");
@@ -33,25 +29,31 @@ where
         #![allow(path_statements)]
         Self::_ASSERT;
         Self {
-            table: [const { none() }; table_size(N)],
+            table: [const { [const { none() }; BLOCK_SIZE] }; N],
             count: 0,
             gen: 1,
         }
     }
 
     const fn idx(key: u32) -> usize {
-        (key as usize) >> (22 - 10)
+        (key as usize) >> (22 - HASH_BITS)
+    }
+
+    #[inline]
+    fn entry(&self, idx: usize) -> &Option<HashTableEntry<T>> {
+        let idx = idx % Self::SIZE;
+        &self.table[idx / BLOCK_SIZE][idx % BLOCK_SIZE]
     }
 
     pub fn set(&mut self, key: u32, value: T) {
         let base = Self::idx(key);
 
         let mut idx = base;
-        let mut v = self.table[base].as_ref().map_or(0, |x| x.gen);
+        let mut v = self.entry(base).as_ref().map_or(0, |x| x.gen);
 
         for i in 0..M {
             let d = (base + i) % Self::SIZE;
-            let entry = self.table[d].as_mut();
+            let entry = self.table[d / BLOCK_SIZE][d % BLOCK_SIZE].as_mut();
             match entry {
                 Some(entry) if entry.key == key => {
                     // update now
@@ -79,11 +81,11 @@ where
             }
         }
 
-        if self.table[idx].is_none() {
+        if self.entry(idx).is_none() {
             self.count += 1;
         }
 
-        self.table[idx] = Some(HashTableEntry {
+        self.table[idx / BLOCK_SIZE][idx % BLOCK_SIZE] = Some(HashTableEntry {
             key,
             value,
             gen: self.gen,
@@ -96,7 +98,7 @@ where
 
     pub fn get(&self, key: u32) -> Option<&T> {
         let base = Self::idx(key);
-        for entry in (0..M).filter_map(|i| self.table[(base + i) % Self::SIZE].as_ref()) {
+        for entry in (0..M).filter_map(|i| self.entry(base + i).as_ref()) {
             if entry.key == key {
                 return Some(&entry.value);
             }
@@ -108,7 +110,7 @@ where
     pub fn get_partial(&self, key: u32) -> impl Iterator<Item = (&u32, &T)> {
         let base = Self::idx(key);
         (0..M)
-            .filter_map(move |i| self.table[(base + i) % Self::SIZE].as_ref())
+            .filter_map(move |i| self.entry(base + i).as_ref())
             .map(|entry| (&entry.key, &entry.value))
     }
 
@@ -117,10 +119,7 @@ where
     }
 }
 
-impl<T, const N: usize, const M: usize> Default for HashTable<T, N, M>
-where
-    [T; table_size(N)]: Sized,
-{
+impl<T, const N: usize, const M: usize> Default for HashTable<T, N, M> {
     fn default() -> Self {
         Self::new()
     }
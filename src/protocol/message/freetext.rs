@@ -3,7 +3,7 @@ use crate::Bitset;
 use core::hint::assert_unchecked;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct F71(pub Bitset<71>);
+pub struct F71(pub Bitset<71, 3>);
 
 impl F71 {
     pub fn from_string(str: &mut [u8]) -> Option<Self> {
@@ -12,6 +12,7 @@ impl G15 {
     const VALUE_RR73: u16 = Self::GRID_MAX + 2;
     const VALUE_V73: u16 = Self::GRID_MAX + 3;
 
+    pub const BLANK: Self = Self(Self::GRID_MAX);
     pub const RRR: Self = Self(Self::GRID_MAX + 1);
     pub const RR73: Self = Self(Self::GRID_MAX + 2);
     pub const V73: Self = Self(Self::GRID_MAX + 3);
@@ -66,6 +67,30 @@ impl G15 {
         Self(val)
     }
 
+    // parse grid locator, signal report, RRR, RR73 or 73
+    pub fn from_string(str: &[u8]) -> Option<Self> {
+        match str {
+            b"RRR" => Some(Self::RRR),
+            b"RR73" => Some(Self::RR73),
+            b"73" => Some(Self::V73),
+            [s @ (b'+' | b'-'), d1 @ b'0'..=b'9', d2 @ b'0'..=b'9'] => {
+                let v = ((d1 - b'0') * 10 + (d2 - b'0')) as i16;
+                let v = if *s == b'-' { -v } else { v };
+                (-30..=99).contains(&v).then(|| Self::from_report(v))
+            }
+            [a, b, c, d] => {
+                let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+                let alpha = b'A'..b'A' + Self::ALPHA_CNT as u8;
+                (alpha.contains(&a)
+                    && alpha.contains(&b)
+                    && c.is_ascii_digit()
+                    && d.is_ascii_digit())
+                .then(|| Self::from_grid_string(&[a, b, *c, *d]))
+            }
+            _ => None,
+        }
+    }
+
     pub fn from_report(report: i16) -> Self {
         debug_assert!((-30..=99).contains(&report));
         let val = (report + 35).unsigned_abs();
@@ -97,12 +122,21 @@ mod tests {
             (b"RRR", G15::RRR),
             (b"RR73", G15::RR73),
             (b"73", G15::V73),
+            (b"", G15::BLANK),
         ];
 
         for (str, g) in testcases {
             let n = g.write_str(&mut buf).unwrap();
             let buf = &buf[..n];
             assert_eq!(String::from_utf8_lossy(buf), String::from_utf8_lossy(str));
+            if !str.is_empty() {
+                assert_eq!(G15::from_string(str), Some(*g));
+            }
         }
+
+        assert_eq!(G15::from_string(b"pm95"), G15::from_string(b"PM95"));
+        assert_eq!(G15::from_string(b"SA00"), None);
+        assert_eq!(G15::from_string(b"-31"), None);
+        assert_eq!(G15::from_string(b"R-15"), None);
     }
 }
@@ -1,4 +1,7 @@
-use crate::{util::write_slice, Bitset};
+use crate::{
+    util::{trim_u8str, write_slice},
+    Bitset,
+};
 
 pub mod chars;
 
@@ -153,14 +156,14 @@ impl Message {
                 // G4ABC/P PA9XYZ JO22
                 writes! { out,
                     call1.write_str(_, hashtable),
-                    if *call1_p { write_slice(out, b"/P") } else { Some(0) },
+                    if *call1_p { write_slice(_, b"/P") } else { Some(0) },
                     write_slice(_, b" "),
 
                     call2.write_str(_, hashtable),
-                    if *call2_p { write_slice(out, b"/P") } else { Some(0) },
+                    if *call2_p { write_slice(_, b"/P") } else { Some(0) },
                     write_slice(_, b" "),
 
-                    if *r { write_slice(out, b"R ") } else { Some(0) },
+                    if *r { write_slice(_, b"R ") } else { Some(0) },
                     grid.write_str(_),
                 }
             }
@@ -277,6 +280,131 @@ impl Message {
         ret
     }
 
+    // parse message text, e.g. "CQ K1ABC FN42", "K1ABC W9XYZ R-15" or "<W9XYZ> PJ4/K1ABC RRR"
+    // falls back to free text; callsigns in the message are registered to `hashtable`
+    pub fn from_text(text: &[u8], hashtable: Option<&mut impl CallsignHashTable>) -> Option<Self> {
+        let text = trim_u8str(text);
+
+        let mut toks: [&[u8]; 5] = [&[]; 5];
+        let mut n = 0;
+        for t in text.split(|&c| c == b' ').filter(|t| !t.is_empty()) {
+            if n == toks.len() {
+                return Self::parse_free_text(text);
+            }
+            toks[n] = t;
+            n += 1;
+        }
+        let toks = &toks[..n];
+
+        let msg = Self::parse_std(toks)
+            .or_else(|| Self::parse_nonstd(toks))
+            .or_else(|| Self::parse_free_text(text))?;
+
+        if let Some(hashtable) = hashtable {
+            if !matches!(msg, Self::FreeText(_)) {
+                for t in toks {
+                    if let Some(call) = unbracket(t) {
+                        hashtable.add(call);
+                    }
+                }
+            }
+            msg.register_callsigns(hashtable);
+        }
+        Some(msg)
+    }
+
+    fn parse_std(toks: &[&[u8]]) -> Option<Self> {
+        // call1 is either "CQ [modifier]", "DE", "QRZ" or a callsign
+        if let [b"CQ", m, rest @ ..] = toks {
+            if let Some(msg) = C28::from_cq(m).and_then(|c| Self::parse_std_rest(c, None, rest)) {
+                return Some(msg);
+            }
+        }
+        let (call1, suffix1) = parse_c28(toks.first()?)?;
+        Self::parse_std_rest(call1, suffix1, &toks[1..])
+    }
+
+    fn parse_std_rest(call1: C28, suffix1: Option<u8>, rest: &[&[u8]]) -> Option<Self> {
+        let (call2, suffix2) = parse_c28(rest.first()?)?;
+
+        let (r, grid) = match &rest[1..] {
+            [] => (false, G15::BLANK),
+            [b"R", g] => (true, G15::from_string(g)?),
+            [g] if g.len() == 4 && g[0] == b'R' && matches!(g[1], b'+' | b'-') => {
+                (true, G15::from_string(&g[1..])?)
+            }
+            [g] => (false, G15::from_string(g)?),
+            _ => return None,
+        };
+
+        match (suffix1, suffix2) {
+            (Some(b'P'), None | Some(b'P')) | (None, Some(b'P')) => Some(Self::EuVhf {
+                call1,
+                call1_p: suffix1.is_some(),
+                call2,
+                call2_p: suffix2.is_some(),
+                r,
+                grid,
+            }),
+            (Some(b'R') | None, Some(b'R') | None) => Some(Self::StdMsg {
+                call1,
+                call1_r: suffix1.is_some(),
+                call2,
+                call2_r: suffix2.is_some(),
+                r,
+                grid,
+            }),
+            _ => None,
+        }
+    }
+
+    fn parse_nonstd(toks: &[&[u8]]) -> Option<Self> {
+        let (a, b, r) = match toks {
+            [b"CQ", call] => {
+                return Some(Self::NonStdCall {
+                    hash: CallsignHash::H12(0),
+                    call: C58::from_call(call)?,
+                    hash_is_second: false,
+                    r: R2::BLANK,
+                    cq: true,
+                });
+            }
+            [a, b] => (a, b, R2::BLANK),
+            [a, b, r] => (a, b, R2::from_string(r)?),
+            _ => return None,
+        };
+
+        // one of the calls is sent as a hash; it is either bracketed or a standard callsign
+        let hash_is_second = if unbracket(a).is_some() {
+            false
+        } else if unbracket(b).is_some() || C28::from_call(b).is_some() {
+            true
+        } else if C28::from_call(a).is_some() {
+            false
+        } else {
+            return None;
+        };
+        let (hashed, call) = if hash_is_second { (b, a) } else { (a, b) };
+        let hashed = unbracket(hashed).unwrap_or(hashed);
+
+        Some(Self::NonStdCall {
+            hash: CallsignHash::H12(hash_callsign(hashed)?.as_h12()),
+            call: C58::from_call(call)?,
+            hash_is_second,
+            r,
+            cq: false,
+        })
+    }
+
+    fn parse_free_text(text: &[u8]) -> Option<Self> {
+        if text.len() > 13 {
+            return None;
+        }
+        let mut buf = [b' '; 13];
+        buf[..text.len()].copy_from_slice(text);
+        F71::from_string(&mut buf).map(Self::FreeText)
+    }
+
     pub fn register_callsigns(&self, hashtable: &mut impl CallsignHashTable) {
         let mut buf = [0; 11];
         match self {
@@ -321,9 +449,35 @@ impl core::fmt::Display for Message {
     }
 }
 
+// "<CALL>" -> "CALL"
+fn unbracket(tok: &[u8]) -> Option<&[u8]> {
+    match tok {
+        [b'<', call @ .., b'>'] => Some(call),
+        _ => None,
+    }
+}
+
+// callsign token with optional "/R" or "/P" suffix
+fn parse_c28(tok: &[u8]) -> Option<(C28, Option<u8>)> {
+    let (tok, suffix) = match tok {
+        [call @ .., b'/', s @ (b'R' | b'P')] => (call, Some(*s)),
+        _ => (tok, None),
+    };
+    let call = match tok {
+        b"DE" => C28::DE,
+        b"QRZ" => C28::QRZ,
+        b"CQ" => C28::CQ,
+        _ => match unbracket(tok) {
+            Some(call) => C28::from_hash(hash_callsign(call)?.as_h22()),
+            None => C28::from_call(tok)?,
+        },
+    };
+    Some((call, suffix))
+}
+
 pub mod callsign;
 use callsign::{
-    hash::{CallsignHash, CallsignHashTable},
+    hash::{hash_callsign, CallsignHash, CallsignHashTable},
     C28, C58,
 };
 
@@ -340,7 +494,7 @@ use super::MessageBits;
 
 // TODO: implement these mocks
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct T71(Bitset<71>); // telemetry data
+pub struct T71(Bitset<71, 3>); // telemetry data
 
 // TODO: implement remaining types; now only frequently used types are implemented
 // pub struct G25(u32); // grid locator 6
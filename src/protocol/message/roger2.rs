@@ -29,6 +29,15 @@ impl R2 {
         }
     }
 
+    pub fn from_string(str: &[u8]) -> Option<Self> {
+        match str {
+            b"RRR" => Some(Self::RRR),
+            b"RR73" => Some(Self::RR73),
+            b"73" => Some(Self::V73),
+            _ => None,
+        }
+    }
+
     pub fn write_str(&self, out: &mut [u8]) -> Option<usize> {
         match self {
             Self::BLANK => write_slice(out, b" "),
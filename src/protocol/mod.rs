@@ -1,4 +1,4 @@
-use crate::Bitset;
+use crate::{bits::num_words, Bitset};
 
 /**
 * Packet structure:
@@ -14,6 +14,9 @@ pub const PAYLOAD_BITS: usize = PAYLOAD_LEN * FSK_DEPTH; // 8-ary
 pub const BODY_BITS: usize = 77;
 pub const CRC_BITS: usize = 14;
 
+pub const SYMBOL_PERIOD: f32 = 0.16; // seconds
+pub const TONE_SPACING: f32 = 6.25; // Hz
+
 pub const FSK_DEPTH: usize = 3;
 pub const FSK_ARITY: usize = 1 << FSK_DEPTH;
 
@@ -23,15 +26,33 @@ pub const MARKER_COSTAS: [usize; COSTAS_SIZE] = [3, 1, 4, 0, 6, 5, 2];
 pub const GRAY_CODE: [u8; FSK_ARITY] = [0, 1, 3, 2, 5, 6, 4, 7];
 pub const GRAY_CODE_INV: [u8; FSK_ARITY] = [0b000, 0b001, 0b011, 0b010, 0b110, 0b100, 0b101, 0b111];
 
-pub type MessageBits = Bitset<BODY_BITS>;
-pub type MessageBitsWithCRC = Bitset<{ BODY_BITS + CRC_BITS }>;
-pub type FullMessageBits = Bitset<PAYLOAD_BITS>;
+pub type MessageBits = Bitset<BODY_BITS, { num_words(BODY_BITS) }>;
+pub type MessageBitsWithCRC = Bitset<{ BODY_BITS + CRC_BITS }, { num_words(BODY_BITS + CRC_BITS) }>;
+pub type FullMessageBits = Bitset<PAYLOAD_BITS, { num_words(PAYLOAD_BITS) }>;
+// one bit per channel symbol
+pub type SymbolMap = Bitset<MESSAGE_LEN, { num_words(MESSAGE_LEN) }>;
 
 pub mod message;
 
+pub mod ap;
 pub mod crc;
 pub mod ldpc;
 
+use message::{callsign::hash::CallsignHashTable, Message};
+
+// Message -> CRC -> LDPC -> channel symbols
+pub fn encode_message(msg: &Message) -> [u8; MESSAGE_LEN] {
+    encode_symbols(&ldpc::encode(&crc::add_crc(msg.encode())))
+}
+
+// same as `encode_message`, parsing the text first
+pub fn encode_text(
+    text: &[u8],
+    hashtable: Option<&mut impl CallsignHashTable>,
+) -> Option<[u8; MESSAGE_LEN]> {
+    Message::from_text(text, hashtable).map(|msg| encode_message(&msg))
+}
+
 pub fn encode_symbols(data: &FullMessageBits) -> [u8; MESSAGE_LEN] {
     let mut ret = [0; MESSAGE_LEN];
 
@@ -57,3 +78,174 @@ pub fn encode_symbols(data: &FullMessageBits) -> [u8; MESSAGE_LEN] {
     }
     ret
 }
+
+// positions of payload symbols in the packet
+#[inline]
+fn payload_pos(i: usize) -> usize {
+    if i < PAYLOAD_HALF_LEN {
+        i + COSTAS_SIZE
+    } else {
+        i + COSTAS_SIZE * 2
+    }
+}
+
+// inverse of `encode_symbols`
+// returns the bits and the number of marker symbols that did not match, or None for a tone
+// out of the 8-FSK range
+pub fn decode_symbols(symbols: &[u8; MESSAGE_LEN]) -> Option<(FullMessageBits, u8)> {
+    if symbols.iter().any(|&tone| tone as usize >= FSK_ARITY) {
+        return None;
+    }
+
+    let mut marker_err = 0;
+    for (i, &v) in MARKER_COSTAS.iter().enumerate() {
+        for ofs in [
+            0,
+            PAYLOAD_HALF_LEN + COSTAS_SIZE,
+            PAYLOAD_LEN + COSTAS_SIZE * 2,
+        ] {
+            if symbols[i + ofs] as usize != v {
+                marker_err += 1;
+            }
+        }
+    }
+
+    let mut ret = FullMessageBits::default();
+    for i in 0..PAYLOAD_LEN {
+        let tone = symbols[payload_pos(i)];
+        let v = GRAY_CODE_INV[tone as usize];
+        ret.set_slice(i * FSK_DEPTH, FSK_DEPTH, v as u32);
+    }
+    Some((ret, marker_err))
+}
+
+// re-encode a decoded message and compare it to the received tones
+// returns a map of symbols that differ; use `count_ones` for the number of errors
+pub fn symbol_errors(decoded: &MessageBitsWithCRC, received: &[u8; MESSAGE_LEN]) -> SymbolMap {
+    let expected = encode_symbols(&ldpc::encode(decoded));
+    let mut ret = SymbolMap::default();
+    for (i, (a, b)) in expected.iter().zip(received.iter()).enumerate() {
+        ret.set(i, a != b);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols() {
+        let msg = crc::add_crc(MessageBits::from([0x01234567, 0x89abcdef, 0xfedc0000]));
+        let cw = ldpc::encode(&msg);
+        let mut symbols = encode_symbols(&cw);
+
+        assert_eq!(decode_symbols(&symbols), Some((cw, 0)));
+        assert_eq!(symbol_errors(&msg, &symbols).count_ones(), 0);
+
+        // corrupt one marker and two payload symbols
+        symbols[2] = (symbols[2] + 1) % 8;
+        symbols[10] = (symbols[10] + 3) % 8;
+        symbols[60] = (symbols[60] + 5) % 8;
+
+        let (bits, marker_err) = decode_symbols(&symbols).unwrap();
+        assert_eq!(marker_err, 1);
+        assert_ne!(bits, cw);
+
+        let map = symbol_errors(&msg, &symbols);
+        assert_eq!(map.count_ones(), 3);
+        assert!(map.get(2) && map.get(10) && map.get(60));
+
+        // a tone out of range is not wrapped into a valid symbol
+        symbols[20] = 8;
+        assert_eq!(decode_symbols(&symbols), None);
+        assert_eq!(symbol_errors(&msg, &symbols).count_ones(), 4);
+    }
+
+    // words, with the brackets of hashed callsigns and the padding of resolved ones removed
+    fn normalize(s: &[u8]) -> Vec<&[u8]> {
+        s.split(|&c| c == b' ')
+            .map(|t| t.strip_prefix(b"<").unwrap_or(t))
+            .map(|t| t.strip_suffix(b">").unwrap_or(t))
+            .map(|t| &t[..t.iter().position(|&c| c == 0).unwrap_or(t.len())])
+            .filter(|t| !t.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_write_eu_vhf() {
+        // suffixes are written after the callsigns, not over the start of the text
+        for text in [&b"G4ABC/P PA9XYZ JO22"[..], b"G4ABC PA9XYZ/P R JO22"] {
+            let msg = Message::from_text(text, None::<&mut ()>).unwrap();
+            assert!(matches!(msg, Message::EuVhf { .. }));
+            let mut buf = [0; 64];
+            let n = msg.write_str(&mut buf, None::<&()>).unwrap();
+            assert_eq!(normalize(&buf[..n]), normalize(text));
+        }
+    }
+
+    #[test]
+    fn test_encode_text_roundtrip() {
+        use crate::{
+            decoder::Decoder,
+            testutil::{add_signal, noise_spectrum, Rng},
+        };
+        use message::callsign::FullCallsign;
+        use std::collections::BTreeMap;
+
+        const TESTCASES: &[&[u8]] = &[
+            b"CQ JA1ZLO PM95",
+            b"CQ DX JA1ZLO PM95",
+            b"JA1ZLO JJ1FYD -12",
+            b"JA1ZLO JJ1FYD R -05",
+            b"JA1ZLO JJ1FYD RR73",
+            b"JA1ZLO/R JJ1FYD 73",
+            b"G4ABC/P PA9XYZ JO22",
+            b"<JA1ZLO> JJ1FYD RRR",
+            b"<JA1ZLO> PJ4/K1ABC RRR",
+            b"PJ4/K1ABC <JA1ZLO> 73",
+            b"CQ PJ4/K1ABC",
+            b"TNX BOB 73 GL",
+        ];
+
+        let mut rng = Rng(1);
+        for &text in TESTCASES {
+            let mut hashtable = BTreeMap::<u32, FullCallsign>::new();
+            let tones = encode_text(text, Some(&mut hashtable)).unwrap();
+
+            let mut spec = noise_spectrum(400, 1.0, &mut rng);
+            add_signal(&mut spec, &tones, 20, 300, 10.0);
+
+            let mut decoder = Decoder::<f32, f32>::new();
+            for row in spec.iter() {
+                decoder.put_spectrum(row);
+            }
+
+            let c = decoder
+                .candidates()
+                .iter()
+                .max_by(|a, b| a.reliability.total_cmp(&b.reliability))
+                .unwrap();
+            assert_eq!((c.dt, c.freq), (20, 300));
+
+            let (bits, err) = ldpc::solve(&c.data);
+            assert_eq!(err, 0);
+            assert!(crc::check_crc(&bits));
+
+            let msg = Message::decode(&bits.with_size()).unwrap();
+            assert_eq!(
+                Some(&msg),
+                Message::from_text(text, None::<&mut ()>).as_ref()
+            );
+
+            let mut buf = [0; 64];
+            let n = msg.write_str(&mut buf, Some(&hashtable)).unwrap();
+            assert_eq!(normalize(&buf[..n]), normalize(text));
+        }
+
+        assert_eq!(
+            Message::from_text(b"JA1ZLO JJ1FYD R-05", None::<&mut ()>),
+            Message::from_text(b"JA1ZLO JJ1FYD R -05", None::<&mut ()>),
+        );
+    }
+}
@@ -0,0 +1,319 @@
+// sample rate conversion to the 8 kHz expected by `Spectrogram`
+//
+// up to two stages are used:
+//   integer decimation: polyphase FIR, only the kept outputs are computed
+//   fractional resampling: polyphase filter bank, interpolating between adjacent phases
+//
+//   input       decimation  fractional
+//   8 kHz       -           -
+//   11.025 kHz  -           441/320
+//   12 kHz      -           3/2
+//   16 kHz      1/2         -
+//   44.1 kHz    1/4         441/320
+//   48 kHz      1/6         -
+//
+// filters pass up to 3.2 kHz (the top of the decoder spectrum) and reject what would alias into it
+// by about 55 dB.
+// both stages are linear phase and compensated, so output timing equals input timing.
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::spectrogram::SAMPLE_RATE;
+use core::f32::consts::PI;
+
+// highest frequency of interest
+const PASS_BAND: f32 = 3200.0;
+
+// filter length = BLACKMAN_WIDTH * rate / transition width
+const BLACKMAN_WIDTH: f32 = 5.5;
+
+const MAX_DECIMATION_TAPS: usize = 169; // 48 kHz
+const MAX_FRACTIONAL_TAPS: usize = 48; // 12 kHz needs 42
+const PHASES: usize = 32;
+
+fn blackman(u: f32) -> f32 {
+    0.42 - 0.5 * (2.0 * PI * u).cos() + 0.08 * (4.0 * PI * u).cos()
+}
+
+// ideal lowpass of cutoff `fc` (relative to the sample rate), at `t` samples from the center
+fn sinc(fc: f32, t: f32) -> f32 {
+    if t == 0.0 {
+        2.0 * fc
+    } else {
+        (2.0 * PI * fc * t).sin() / (PI * t)
+    }
+}
+
+// polyphase FIR decimator
+#[derive(Clone)]
+pub(crate) struct Decimator {
+    factor: usize,
+    taps: usize,
+    coeffs: [f32; MAX_DECIMATION_TAPS],
+    history: [f32; MAX_DECIMATION_TAPS],
+    pos: usize,
+    // inputs to skip until the next output
+    wait: usize,
+}
+
+impl Decimator {
+    // passes up to `pass` Hz, rejecting what would alias into it
+    pub fn new(rate: u32, factor: usize, pass: f32) -> Self {
+        let out_rate = rate as f32 / factor as f32;
+        let stop = out_rate - pass;
+        let len = BLACKMAN_WIDTH * rate as f32 / (stop - pass);
+        // odd length, with the center on the output grid
+        let half = (len as usize).div_ceil(2 * factor) * factor;
+        let taps = 2 * half + 1;
+        assert!(taps <= MAX_DECIMATION_TAPS);
+
+        let fc = (pass + stop) / 2.0 / rate as f32;
+        let mut coeffs = [0.0; MAX_DECIMATION_TAPS];
+        for (k, c) in coeffs.iter_mut().enumerate().take(taps) {
+            let t = k as f32 - half as f32;
+            *c = sinc(fc, t) * blackman(k as f32 / (taps - 1) as f32);
+        }
+        let sum: f32 = coeffs.iter().sum();
+        coeffs.iter_mut().for_each(|c| *c /= sum);
+
+        Self {
+            factor,
+            taps,
+            coeffs,
+            history: [0.0; MAX_DECIMATION_TAPS],
+            pos: 0,
+            // the first output is centered at the first input
+            wait: half,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; MAX_DECIMATION_TAPS];
+        self.pos = 0;
+        self.wait = self.taps / 2;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> Option<f32> {
+        self.history[self.pos] = x;
+        self.pos = (self.pos + 1) % self.taps;
+        if self.wait > 0 {
+            self.wait -= 1;
+            return None;
+        }
+        self.wait = self.factor - 1;
+
+        // oldest first
+        let (new, old) = self.history[..self.taps].split_at(self.pos);
+        let y = old
+            .iter()
+            .chain(new.iter())
+            .zip(self.coeffs.iter())
+            .map(|(x, c)| x * c)
+            .sum();
+        Some(y)
+    }
+}
+
+// polyphase resampler for ratios between 1 and 2
+#[derive(Clone)]
+struct Fractional {
+    // input samples per output sample = step / den
+    step: i32,
+    den: i32,
+    taps: usize,
+    // kernel at offsets (j - taps / 2 + 1) - phase / PHASES
+    table: [[f32; MAX_FRACTIONAL_TAPS]; PHASES + 1],
+    history: [f32; MAX_FRACTIONAL_TAPS],
+    pos: usize,
+    // time of the next output relative to the newest input, in 1/den samples
+    next: i32,
+}
+
+impl Fractional {
+    fn new(rate: u32, step: i32, den: i32) -> Self {
+        let stop = SAMPLE_RATE as f32 - PASS_BAND;
+        let len = BLACKMAN_WIDTH * rate as f32 / (stop - PASS_BAND);
+        let taps = (len as usize).div_ceil(2) * 2;
+        assert!(taps <= MAX_FRACTIONAL_TAPS);
+
+        let fc = (PASS_BAND + stop) / 2.0 / rate as f32;
+        let mut table = [[0.0; MAX_FRACTIONAL_TAPS]; PHASES + 1];
+        for (phase, row) in table.iter_mut().enumerate() {
+            for (j, c) in row.iter_mut().enumerate().take(taps) {
+                let t = (j as f32 - (taps / 2) as f32 + 1.0) - phase as f32 / PHASES as f32;
+                *c = sinc(fc, t) * blackman((t + (taps / 2) as f32) / taps as f32);
+            }
+            let sum: f32 = row.iter().sum();
+            row.iter_mut().for_each(|c| *c /= sum);
+        }
+
+        Self {
+            step,
+            den,
+            taps,
+            table,
+            history: [0.0; MAX_FRACTIONAL_TAPS],
+            pos: 0,
+            // the first output is at the first input
+            next: den,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history = [0.0; MAX_FRACTIONAL_TAPS];
+        self.pos = 0;
+        self.next = self.den;
+    }
+
+    #[inline]
+    fn process(&mut self, x: f32) -> Option<f32> {
+        self.history[self.pos] = x;
+        self.pos = (self.pos + 1) % self.taps;
+        self.next -= self.den;
+
+        // wait until the kernel is covered
+        let half = (self.taps / 2) as i32;
+        if self.next >= (1 - half) * self.den {
+            return None;
+        }
+
+        // sub-sample position, between two phases
+        let frac = self.next.rem_euclid(self.den) as f32 / self.den as f32 * PHASES as f32;
+        let phase = (frac as usize).min(PHASES - 1);
+        let mu = frac - phase as f32;
+        // taps start at this many samples before the newest
+        let ofs = (-self.next.div_euclid(self.den) + half - 1) as usize;
+        let start = (self.pos + self.taps - 1 - ofs) % self.taps;
+
+        let (a, b) = (&self.table[phase], &self.table[phase + 1]);
+        let mut y = 0.0;
+        for j in 0..self.taps {
+            let c = a[j] + (b[j] - a[j]) * mu;
+            y += c * self.history[(start + j) % self.taps];
+        }
+        self.next += self.step;
+        Some(y)
+    }
+}
+
+/// converts supported sample rates to 8 kHz
+/// no allocation; about 8 kB
+#[derive(Clone)]
+pub struct Resampler {
+    decimator: Option<Decimator>,
+    fractional: Option<Fractional>,
+}
+
+impl Default for Resampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resampler {
+    pub const RATES: [u32; 6] = [8000, 11025, 12000, 16000, 44100, 48000];
+
+    /// pass through 8 kHz input
+    pub const fn new() -> Self {
+        Self {
+            decimator: None,
+            fractional: None,
+        }
+    }
+
+    /// None if `rate` is not one of `RATES`
+    pub fn with_rate(rate: u32) -> Option<Self> {
+        let (decimator, fractional) = match rate {
+            8000 => (None, None),
+            11025 => (None, Some(Fractional::new(11025, 441, 320))),
+            12000 => (None, Some(Fractional::new(12000, 3, 2))),
+            16000 => (Some(Decimator::new(16000, 2, PASS_BAND)), None),
+            44100 => (
+                Some(Decimator::new(44100, 4, PASS_BAND)),
+                Some(Fractional::new(11025, 441, 320)),
+            ),
+            48000 => (Some(Decimator::new(48000, 6, PASS_BAND)), None),
+            _ => return None,
+        };
+        Some(Self {
+            decimator,
+            fractional,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(d) = &mut self.decimator {
+            d.reset();
+        }
+        if let Some(f) = &mut self.fractional {
+            f.reset();
+        }
+    }
+
+    /// feed one input sample; returns an output sample when one is ready
+    #[inline]
+    pub fn process(&mut self, x: f32) -> Option<f32> {
+        let x = match &mut self.decimator {
+            Some(d) => d.process(x)?,
+            None => x,
+        };
+        match &mut self.fractional {
+            Some(f) => f.process(x),
+            None => Some(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn run(rate: u32, freq: f32, amp: f32, len: usize) -> Vec<f32> {
+        let mut r = Resampler::with_rate(rate).unwrap();
+        (0..len)
+            .filter_map(|i| {
+                let t = i as f32 / rate as f32;
+                r.process(amp * (2.0 * PI * freq * t).sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_passband() {
+        for rate in Resampler::RATES {
+            let out = run(rate, 1000.0, 0.5, rate as usize);
+            // the last outputs wait for the filter to fill
+            assert!(SAMPLE_RATE as usize - out.len() <= 20, "{}", rate);
+
+            // same amplitude and timing as the input; skip the start-up
+            for (i, &y) in out.iter().enumerate().skip(100) {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let expected = 0.5 * (2.0 * PI * 1000.0 * t).sin();
+                assert!((y - expected).abs() < 0.01, "{} Hz at {}: {}", rate, i, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stopband() {
+        // 5 kHz would alias to 3 kHz at 8 kHz; 13 kHz aliases into the passband at the decimated rates
+        for rate in Resampler::RATES.into_iter().skip(1) {
+            for freq in [5000.0, 13000.0] {
+                if freq >= rate as f32 / 2.0 {
+                    continue;
+                }
+                let out = run(rate, freq, 1.0, rate as usize);
+                let peak = out.iter().skip(100).fold(0.0f32, |a, &y| a.max(y.abs()));
+                assert!(peak < 3e-3, "{} Hz at {} Hz: {}", freq, rate, peak);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(Resampler::with_rate(22050).is_none());
+    }
+}
@@ -0,0 +1,451 @@
+// audio front end: PCM samples to the magnitude spectra expected by `Decoder::put_spectrum`
+//
+// input is 8 kHz mono, or one of `Resampler::RATES` converted to 8 kHz. every 40 ms (320 samples) the last 160 ms (1280 samples) are
+// Hann windowed, zero padded to 2560 samples and transformed, giving 3.125 Hz per bin.
+//
+// scaling: samples are taken as full scale at 1.0 (`i16` is divided by 32768),
+// and magnitudes are multiplied by 2 / (window sum), so that a sine of amplitude A
+// centered on a bin reads A at that bin. i.e. a full scale tone reads 1.0.
+
+use crate::{
+    decoder::{Decoder, SPECTRUM_SIZE},
+    float::{FloatS, FloatU},
+    resample::Resampler,
+};
+use core::ops::{Add, Mul, Sub};
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+pub const SAMPLE_RATE: u32 = 8000;
+pub const WINDOW_SIZE: usize = 1280; // 160 ms
+pub const HOP_SIZE: usize = 320; // 40 ms
+
+const FFT_SIZE: usize = WINDOW_SIZE * 2; // real FFT, zero padded
+const HALF_SIZE: usize = FFT_SIZE / 2; // complex FFT size
+const RADIX2_SIZE: usize = 256;
+const RADIX2_BITS: u32 = RADIX2_SIZE.trailing_zeros();
+const RADIX5_SIZE: usize = HALF_SIZE / RADIX2_SIZE;
+
+// 2 / sum of the Hann window
+const SCALE: f32 = 4.0 / WINDOW_SIZE as f32;
+
+const QUARTER: usize = FFT_SIZE / 4;
+
+// cos(2 pi i / FFT_SIZE) for the first quarter wave
+static COS_TABLE: [f32; QUARTER + 1] = {
+    let mut table = [0.0; QUARTER + 1];
+    let mut i = 0;
+    while i <= QUARTER {
+        table[i] = const_cos(2.0 * core::f64::consts::PI * i as f64 / FFT_SIZE as f64) as f32;
+        i += 1;
+    }
+    table
+};
+
+// Taylor series; fine for 0..=pi/2
+const fn const_cos(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut n = 1;
+    while n < 20 {
+        term *= -x * x / ((2 * n - 1) * (2 * n)) as f64;
+        sum += term;
+        n += 1;
+    }
+    sum
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    #[inline]
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    #[inline]
+    pub fn scale(self, k: f32) -> Self {
+        Self::new(self.re * k, self.im * k)
+    }
+
+    #[inline]
+    pub fn norm(self) -> f32 {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+// exp(-2 pi i k / 2560)
+pub(crate) fn twiddle(k: usize) -> Complex {
+    let k = k % FFT_SIZE;
+    let (q, r) = (k / QUARTER, k % QUARTER);
+    let (c, s) = (COS_TABLE[r], COS_TABLE[QUARTER - r]);
+    let (cos, sin) = match q {
+        0 => (c, s),
+        1 => (-s, c),
+        2 => (-c, -s),
+        _ => (s, -c),
+    };
+    Complex::new(cos, -sin)
+}
+
+// in-place radix-2 FFT of RADIX2_SIZE points
+fn fft_radix2(data: &mut [Complex]) {
+    debug_assert!(data.len() == RADIX2_SIZE);
+
+    for i in 0..RADIX2_SIZE {
+        let j = i.reverse_bits() >> (usize::BITS - RADIX2_BITS);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= RADIX2_SIZE {
+        let step = FFT_SIZE / len;
+        for chunk in data.chunks_exact_mut(len) {
+            let (a, b) = chunk.split_at_mut(len / 2);
+            for (k, (a, b)) in a.iter_mut().zip(b.iter_mut()).enumerate() {
+                let t = *b * twiddle(k * step);
+                *b = *a - t;
+                *a = *a + t;
+            }
+        }
+        len *= 2;
+    }
+}
+
+// complex FFT of HALF_SIZE points, as RADIX5_SIZE interleaved radix-2 FFTs
+#[derive(Clone)]
+pub(crate) struct Fft {
+    work: [Complex; HALF_SIZE],
+}
+
+impl Fft {
+    pub const fn new() -> Self {
+        Self {
+            work: [Complex::new(0.0, 0.0); HALF_SIZE],
+        }
+    }
+
+    // transform `input(n)` for n in 0..SIZE
+    pub fn load(&mut self, input: impl Fn(usize) -> Complex) {
+        // block r holds x[RADIX5_SIZE m + r]
+        for r in 0..RADIX5_SIZE {
+            let block = &mut self.work[r * RADIX2_SIZE..(r + 1) * RADIX2_SIZE];
+            for (m, x) in block.iter_mut().enumerate() {
+                *x = input(RADIX5_SIZE * m + r);
+            }
+            fft_radix2(block);
+        }
+    }
+
+    // bin `k` of the last transform, combining the radix-2 blocks
+    pub fn bin(&self, k: usize) -> Complex {
+        let k = k % HALF_SIZE;
+        let mut sum = Complex::default();
+        for r in 0..RADIX5_SIZE {
+            // exp(-2 pi i r k / HALF_SIZE)
+            let w = twiddle(2 * r * k);
+            sum = sum + w * self.work[r * RADIX2_SIZE + k % RADIX2_SIZE];
+        }
+        sum
+    }
+}
+
+/// sample types accepted by `Spectrogram`
+pub trait Sample: Copy {
+    /// full scale is 1.0
+    fn to_f32(self) -> f32;
+}
+
+impl Sample for f32 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for i16 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32 / 32768.0
+    }
+}
+
+/// short time Fourier transform feeding `Decoder`
+/// no allocation; about 23 kB, so it can be placed in a static buffer
+#[derive(Clone)]
+pub struct Spectrogram {
+    resampler: Resampler,
+    // ring buffer of the last WINDOW_SIZE samples
+    samples: [f32; WINDOW_SIZE],
+    pos: usize,
+    // spectra output so far
+    hops: usize,
+    fft: Fft,
+}
+
+impl Default for Spectrogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Spectrogram {
+    /// for 8 kHz input
+    pub const fn new() -> Self {
+        Self {
+            resampler: Resampler::new(),
+            samples: [0.0; WINDOW_SIZE],
+            pos: 0,
+            hops: 0,
+            fft: Fft::new(),
+        }
+    }
+
+    /// for input at `rate`; None if the rate is not one of `Resampler::RATES`
+    pub fn with_rate(rate: u32) -> Option<Self> {
+        Some(Self {
+            resampler: Resampler::with_rate(rate)?,
+            ..Self::new()
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.samples = [0.0; WINDOW_SIZE];
+        self.pos = 0;
+        self.hops = 0;
+    }
+
+    /// feed samples; `decoder` gets a spectrum every 40 ms, picked to fit its grid
+    pub fn put_samples<
+        S: Sample,
+        SpecFloat: FloatU,
+        LLRFloat: FloatS,
+        const WIDTH: usize,
+        const FREQ_OS: usize,
+        const TIME_OS: usize,
+        const ROWS: usize,
+        const CANDIDATES: usize,
+    >(
+        &mut self,
+        samples: &[S],
+        decoder: &mut Decoder<SpecFloat, LLRFloat, WIDTH, FREQ_OS, TIME_OS, ROWS, CANDIDATES>,
+    ) {
+        let mut hops = self.hops;
+        self.put_samples_with(samples, |s| {
+            decoder.put_fine_spectrum(s, hops);
+            hops = hops.wrapping_add(1);
+        });
+        self.hops = hops;
+    }
+
+    /// feed samples; `f` is called with the magnitude spectrum every 40 ms
+    pub fn put_samples_with<S: Sample>(
+        &mut self,
+        samples: &[S],
+        mut f: impl FnMut(&[f32; SPECTRUM_SIZE]),
+    ) {
+        for &s in samples {
+            let Some(x) = self.resampler.process(s.to_f32()) else {
+                continue;
+            };
+            self.samples[self.pos] = x;
+            self.pos = (self.pos + 1) % WINDOW_SIZE;
+            if self.pos.is_multiple_of(HOP_SIZE) {
+                let mut spectrum = [0.0; SPECTRUM_SIZE];
+                self.transform(&mut spectrum);
+                f(&spectrum);
+            }
+        }
+    }
+
+    // magnitude spectrum of the current window
+    fn transform(&mut self, out: &mut [f32; SPECTRUM_SIZE]) {
+        // real input of FFT_SIZE is packed into complex of HALF_SIZE: z[n] = x[2n] + i x[2n+1]
+        let (samples, pos) = (&self.samples, self.pos);
+        self.fft.load(|n| {
+            if 2 * n < WINDOW_SIZE {
+                Complex::new(
+                    windowed(samples, pos, 2 * n),
+                    windowed(samples, pos, 2 * n + 1),
+                )
+            } else {
+                Complex::default()
+            }
+        });
+
+        for (k, x) in out.iter_mut().enumerate() {
+            let z0 = self.fft.bin(k);
+            let z1 = self.fft.bin(HALF_SIZE - k).conj();
+            let even = z0 + z1;
+            let odd = (z0 - z1) * Complex::new(0.0, -1.0);
+            // 0.5 for the even/odd split
+            let v = (even + twiddle(k) * odd).norm() * 0.5;
+            *x = v * SCALE;
+        }
+    }
+}
+
+// n-th sample of the ring buffer `samples`, oldest first, with Hann window applied
+#[inline]
+fn windowed(samples: &[f32; WINDOW_SIZE], pos: usize, n: usize) -> f32 {
+    let w = 0.5 - 0.5 * twiddle(2 * n).re;
+    samples[(pos + n) % WINDOW_SIZE] * w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::encode_text,
+        testutil::{synth_audio, Rng},
+    };
+    use std::{collections::BTreeMap, vec::Vec};
+
+    // reference: direct DFT of the zero padded window
+    fn dft(s: &Spectrogram, k: usize) -> f32 {
+        let mut sum = Complex::default();
+        for n in 0..WINDOW_SIZE {
+            sum = sum + twiddle(k * n) * Complex::new(windowed(&s.samples, s.pos, n), 0.0);
+        }
+        sum.norm() * SCALE
+    }
+
+    #[test]
+    fn test_twiddle() {
+        for k in [0, 1, 100, 640, 700, 1280, 1900, 2559, 2560 + 3] {
+            let a = -2.0 * core::f64::consts::PI * k as f64 / FFT_SIZE as f64;
+            let w = twiddle(k);
+            assert!((w.re as f64 - a.cos()).abs() < 1e-6, "{}", k);
+            assert!((w.im as f64 - a.sin()).abs() < 1e-6, "{}", k);
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut rng = Rng(1);
+        let samples = (0..WINDOW_SIZE + 123)
+            .map(|_| rng.uniform() - 0.5)
+            .collect::<Vec<_>>();
+
+        let mut s = Spectrogram::new();
+        let mut last = [0.0; SPECTRUM_SIZE];
+        s.put_samples_with(&samples, |x| last = *x);
+        // rewind to the last emitted window
+        let mut s2 = Spectrogram::new();
+        s2.put_samples_with(&samples[..samples.len() / HOP_SIZE * HOP_SIZE], |_| {});
+
+        for k in [0, 1, 2, 17, 320, 511, 512, 1000, 1023] {
+            let d = dft(&s2, k);
+            assert!(
+                (last[k] - d).abs() < 1e-4,
+                "bin {}: {} vs {}",
+                k,
+                last[k],
+                d
+            );
+        }
+    }
+
+    #[test]
+    fn test_scaling() {
+        // 0.5 amplitude tone at bin 320 (1000 Hz), as i16
+        let samples = (0..WINDOW_SIZE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (16384.0 * (2.0 * core::f32::consts::PI * 1000.0 * t).sin()) as i16
+            })
+            .collect::<Vec<_>>();
+
+        let mut s = Spectrogram::new();
+        let mut spectra = Vec::new();
+        s.put_samples_with(&samples, |x| spectra.push(*x));
+        assert_eq!(spectra.len(), WINDOW_SIZE / HOP_SIZE);
+
+        let last = spectra.last().unwrap();
+        let peak = (0..SPECTRUM_SIZE)
+            .max_by(|&a, &b| last[a].total_cmp(&last[b]))
+            .unwrap();
+        assert_eq!(peak, 320);
+        assert!((last[320] - 0.5).abs() < 1e-3, "{}", last[320]);
+    }
+
+    #[test]
+    fn test_decode_audio() {
+        let text = b"CQ JA1ZLO PM95";
+        let tones = encode_text(text, None::<&mut ()>).unwrap();
+
+        // same result regardless of the input rate
+        let mut expected = None;
+        for rate in Resampler::RATES {
+            let audio = synth_audio(&tones, rate, 1000.0, 0.51, 0.1, 0.3, &mut Rng(3));
+
+            let mut s = Spectrogram::with_rate(rate).unwrap();
+            let mut decoder = Decoder::<f32, f32>::new();
+            s.put_samples(&audio, &mut decoder);
+
+            let mut hashtable = BTreeMap::new();
+            let decodes = decoder.decode_all(&mut hashtable).collect::<Vec<_>>();
+            assert_eq!(decodes.len(), 1, "{} Hz", rate);
+            let d = &decodes[0];
+            assert!((d.freq - 1000.0).abs() <= 0.2, "{} Hz: {}", rate, d.freq);
+            assert!((d.dt - 0.51).abs() <= 0.01, "{} Hz: {}", rate, d.dt);
+            assert_eq!(
+                Some(&d.message),
+                crate::protocol::message::Message::from_text(text, None::<&mut ()>).as_ref()
+            );
+
+            // the resampler filters move the position by up to 10 ms and 0.2 Hz
+            let (dt, tenths) = (d.dt, (d.freq * 10.0).round() as i32);
+            let key = (d.errors, d.payload);
+            let first = *expected.get_or_insert((dt, tenths, key));
+            assert_eq!(first.2, key, "{} Hz", rate);
+            assert!(
+                (dt - first.0).abs() <= 0.01,
+                "{} Hz: {} vs {}",
+                rate,
+                dt,
+                first.0
+            );
+            assert!((tenths - first.1).abs() <= 2, "{} Hz: {}", rate, d.freq);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// helpers to synthesize decoder input in tests
+
+pub use crate::decoder::SPECTRUM_SIZE;
+use crate::protocol::{MESSAGE_LEN, SYMBOL_PERIOD, TONE_SPACING};
+use std::vec::Vec;
+
+pub struct Rng(pub u32);
+
+impl Rng {
+    // xorshift32
+    pub fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // uniform in [0, 1)
+    pub fn uniform(&mut self) -> f32 {
+        (self.next() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+// magnitude spectra (3.125 Hz/bin, 160 ms window, 40 ms step) with a noise floor of about `noise`
+pub fn noise_spectrum(rows: usize, noise: f32, rng: &mut Rng) -> Vec<[f32; SPECTRUM_SIZE]> {
+    (0..rows)
+        .map(|_| core::array::from_fn(|_| noise * (0.5 + rng.uniform())))
+        .collect()
+}
+
+// add an FT8 signal whose first symbol starts at row `start` and tone 0 is at bin `bin`
+pub fn add_signal(
+    spec: &mut [[f32; SPECTRUM_SIZE]],
+    tones: &[u8; MESSAGE_LEN],
+    start: usize,
+    bin: usize,
+    amp: f32,
+) {
+    for (t, row) in spec.iter_mut().enumerate() {
+        for (s, &tone) in tones.iter().enumerate() {
+            // overlap of the window with the symbol, in 40 ms steps
+            let ofs = (t as isize - (start + s * 4) as isize).unsigned_abs();
+            if ofs < 4 {
+                row[bin + tone as usize * 2] += amp * (4 - ofs) as f32 / 4.0;
+            }
+        }
+    }
+}
+
+// 15 s of audio with an FT8 signal starting at `start` seconds, tone 0 at `freq` Hz
+// continuous phase FSK of amplitude `amp`, plus uniform noise of RMS `noise`
+pub fn synth_audio(
+    tones: &[u8; MESSAGE_LEN],
+    rate: u32,
+    freq: f32,
+    start: f32,
+    amp: f32,
+    noise: f32,
+    rng: &mut Rng,
+) -> Vec<f32> {
+    let len = rate as usize * 15;
+    let symbol_len = (SYMBOL_PERIOD * rate as f32) as usize;
+    let start = (start * rate as f32) as usize;
+
+    let mut phase = 0.0f32;
+    (0..len)
+        .map(|i| {
+            let mut v = noise * (rng.uniform() - 0.5) * 12f32.sqrt();
+            if let Some(&tone) = i.checked_sub(start).and_then(|j| tones.get(j / symbol_len)) {
+                let f = freq + tone as f32 * TONE_SPACING;
+                phase += 2.0 * core::f32::consts::PI * f / rate as f32;
+                phase %= 2.0 * core::f32::consts::PI;
+                v += amp * phase.sin();
+            }
+            v
+        })
+        .collect()
+}
+
+// same as `synth_audio`, as complex I/Q with the signal at `freq` Hz (may be negative)
+pub fn synth_iq(
+    tones: &[u8; MESSAGE_LEN],
+    rate: u32,
+    freq: f32,
+    start: f32,
+    amp: f32,
+    noise: f32,
+    rng: &mut Rng,
+) -> Vec<[f32; 2]> {
+    let len = rate as usize * 15;
+    let symbol_len = (SYMBOL_PERIOD * rate as f32) as usize;
+    let start = (start * rate as f32) as usize;
+
+    let mut phase = 0.0f32;
+    (0..len)
+        .map(|i| {
+            let mut v = [0.0, 0.0].map(|_: f32| noise * (rng.uniform() - 0.5) * 6f32.sqrt());
+            if let Some(&tone) = i.checked_sub(start).and_then(|j| tones.get(j / symbol_len)) {
+                let f = freq + tone as f32 * TONE_SPACING;
+                phase += 2.0 * core::f32::consts::PI * f / rate as f32;
+                phase %= 2.0 * core::f32::consts::PI;
+                v[0] += amp * phase.cos();
+                v[1] += amp * phase.sin();
+            }
+            v
+        })
+        .collect()
+}